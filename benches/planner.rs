@@ -0,0 +1,95 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use criterion::{criterion_group, criterion_main, Criterion};
+use organize::category::categorize;
+use organize::extension::Aliases;
+use organize::planner::{date_folder, DateLayout, Granularity, Levels, Trace, WeekLabel};
+use organize::week::{start_of_week, week_of_month, week_of_year, WeekStart, WeekSystem};
+use std::hint::black_box;
+use std::path::{Path, PathBuf};
+
+const EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "mp4", "pdf", "docx", "mp3", "zip", "txt", "rs"];
+
+/// Synthetic file metadata: a path and a modification time spread over a few years.
+fn synthetic_files(count: usize) -> Vec<(PathBuf, NaiveDateTime)> {
+    let start = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    (0..count)
+        .map(|i| {
+            let path = PathBuf::from(format!("file-{}.{}", i, EXTENSIONS[i % EXTENSIONS.len()]));
+            (path, start + Duration::hours(i as i64 * 7))
+        })
+        .collect()
+}
+
+fn date_mode(c: &mut Criterion) {
+    let files = synthetic_files(10_000);
+    let mut group = c.benchmark_group("date mode folders");
+    for (label, week_label) in [("date", WeekLabel::Date), ("ordinal", WeekLabel::Ordinal), ("number", WeekLabel::Number)] {
+        // The default layout, apart from the week label
+        let layout = DateLayout {
+            levels: Levels::Nested,
+            granularity: Granularity::Week,
+            week_label,
+            week_system: WeekSystem::Us,
+            week_start: WeekStart::Sunday,
+            year_format: None,
+            month_format: None,
+            week_format: None,
+            fiscal_year_start: None,
+        };
+        group.bench_function(label, |b| {
+            b.iter(|| {
+                for (_, datetime) in &files {
+                    black_box(date_folder(datetime, Path::new("organized"), &layout, &mut Trace::off()));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn week_starts(c: &mut Criterion) {
+    let files = synthetic_files(10_000);
+    let mut group = c.benchmark_group("week starts");
+    for (label, week_start) in [("sunday", WeekStart::Sunday), ("monday", WeekStart::Monday)] {
+        group.bench_function(label, |b| {
+            b.iter(|| {
+                for (_, datetime) in &files {
+                    black_box(start_of_week(datetime, week_start));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn extension_mode(c: &mut Criterion) {
+    let files = synthetic_files(10_000);
+    let aliases = Aliases::new(&[]);
+    c.bench_function("extension mode folders", |b| {
+        b.iter(|| {
+            for (path, _) in &files {
+                let ext = aliases.normalized_extension(path).unwrap_or_default();
+                black_box(categorize(&ext).folder_name());
+            }
+        })
+    });
+}
+
+fn week_numbers(c: &mut Criterion) {
+    let files = synthetic_files(10_000);
+    let mut group = c.benchmark_group("week numbers");
+    for (label, system) in [("iso", WeekSystem::Iso), ("us", WeekSystem::Us)] {
+        group.bench_function(label, |b| {
+            b.iter(|| {
+                for (_, datetime) in &files {
+                    black_box(week_of_month(datetime, system, WeekStart::Sunday));
+                    black_box(week_of_year(datetime, system, WeekStart::Sunday));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, date_mode, week_starts, extension_mode, week_numbers);
+criterion_main!(benches);
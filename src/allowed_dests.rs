@@ -0,0 +1,58 @@
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+/// The destination folders files may be moved into, from an `--allowed-dests` file.
+///
+/// Folders are kept relative to the target directory, so `./2024/March/` and `2024/March`
+/// name the same folder.
+pub struct AllowedDests(HashSet<PathBuf>);
+
+impl AllowedDests {
+    /// Loads one folder per line, skipping blank lines and `#` comments.
+    ///
+    /// Relative folders are taken from the target directory `root`; absolute ones must lie inside it.
+    pub fn load(path: &Path, root: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read allowed destinations {}: {}", path.display(), err))?;
+        let canonical_root = std::fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+
+        let mut folders = HashSet::new();
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let folder = Path::new(line);
+            let folder = if folder.is_absolute() {
+                folder.strip_prefix(&canonical_root).map_err(|_| {
+                    format!("Allowed destination on line {} is outside {}: {}", index + 1, root.display(), line)
+                })?
+            } else {
+                folder
+            };
+            folders.insert(normalize(folder));
+        }
+        Ok(AllowedDests(folders))
+    }
+
+    /// Whether `folder`, a destination under `root`, is one of the allowed folders.
+    pub fn allows(&self, folder: &Path, root: &Path) -> bool {
+        let relative = folder.strip_prefix(root).unwrap_or(folder);
+        self.0.contains(&normalize(relative))
+    }
+}
+
+/// Lexically removes `.` components and resolves `..` against the preceding component.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
@@ -0,0 +1,38 @@
+/// Broad kinds of files, used to sort files into top-level bins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Images,
+    Videos,
+    Documents,
+    Audio,
+    Archives,
+    Other,
+}
+
+impl Category {
+    /// The folder name used for this category.
+    pub fn folder_name(self) -> &'static str {
+        match self {
+            Category::Images => "Images",
+            Category::Videos => "Videos",
+            Category::Documents => "Documents",
+            Category::Audio => "Audio",
+            Category::Archives => "Archives",
+            Category::Other => "Other",
+        }
+    }
+}
+
+/// Maps a file extension (without the dot, any case) to its category.
+pub fn categorize(ext: &str) -> Category {
+    match ext.to_lowercase().as_str() {
+        "jpg" | "jpeg" | "jpe" | "png" | "gif" | "bmp" | "tif" | "tiff" | "webp" | "heic" | "heif" | "svg"
+        | "raw" | "cr2" | "nef" | "arw" | "dng" => Category::Images,
+        "mp4" | "mov" | "avi" | "mkv" | "webm" | "wmv" | "flv" | "m4v" | "mpg" | "mpeg" | "3gp" => Category::Videos,
+        "pdf" | "doc" | "docx" | "xls" | "xlsx" | "ppt" | "pptx" | "odt" | "ods" | "odp" | "txt" | "rtf" | "md"
+        | "csv" | "epub" => Category::Documents,
+        "mp3" | "wav" | "flac" | "aac" | "ogg" | "m4a" | "wma" | "opus" | "aiff" => Category::Audio,
+        "zip" | "tar" | "gz" | "tgz" | "bz2" | "xz" | "7z" | "rar" | "zst" => Category::Archives,
+        _ => Category::Other,
+    }
+}
@@ -0,0 +1,61 @@
+use std::path::Path;
+
+/// Side length, in pixels, images are shrunk to before their colors are averaged.
+const THUMBNAIL_SIZE: u32 = 16;
+
+/// Saturation below which an image counts as `grayscale`.
+const GRAYSCALE_SATURATION: f32 = 0.15;
+
+/// Hue ranges, in degrees, and the folder each one maps to. Anything past the last
+/// bound wraps around to `red`.
+const HUE_BUCKETS: &[(f32, &str)] = &[
+    (15.0, "red"),
+    (45.0, "orange"),
+    (70.0, "yellow"),
+    (165.0, "green"),
+    (195.0, "cyan"),
+    (255.0, "blue"),
+    (290.0, "purple"),
+    (335.0, "pink"),
+];
+
+/// Returns the color bucket of an image's dominant color, or `None` if it cannot be decoded.
+///
+/// The image is downscaled to a thumbnail first, so large photos decode quickly.
+pub fn dominant_color(path: &Path) -> Option<&'static str> {
+    let image = image::open(path).ok()?;
+    let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgb8();
+    let pixels = thumbnail.pixels().len() as f32;
+    if pixels == 0.0 {
+        return None;
+    }
+    let [red, green, blue] = thumbnail.pixels().fold([0.0_f32; 3], |mut sum, pixel| {
+        for (total, channel) in sum.iter_mut().zip(pixel.0) {
+            *total += f32::from(channel);
+        }
+        sum
+    });
+    Some(bucket(red / pixels, green / pixels, blue / pixels))
+}
+
+/// Names the bucket an average RGB color (each channel 0-255) falls in.
+fn bucket(red: f32, green: f32, blue: f32) -> &'static str {
+    let max = red.max(green).max(blue);
+    let min = red.min(green).min(blue);
+    let chroma = max - min;
+    if max == 0.0 || chroma / max < GRAYSCALE_SATURATION {
+        return "grayscale";
+    }
+
+    let hue = if max == red {
+        60.0 * ((green - blue) / chroma).rem_euclid(6.0)
+    } else if max == green {
+        60.0 * ((blue - red) / chroma + 2.0)
+    } else {
+        60.0 * ((red - green) / chroma + 4.0)
+    };
+    HUE_BUCKETS
+        .iter()
+        .find(|(bound, _)| hue < *bound)
+        .map_or("red", |(_, name)| name)
+}
@@ -0,0 +1,80 @@
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, Command, Parser};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Parses the command line, filling in options it leaves unset from the `--config` file.
+///
+/// Each key of the TOML file is the long name of an option, such as `week-start = "monday"`
+/// or `exclude = ["*.part"]`; `dir` sets the directory. Options given on the command line take
+/// precedence over the file, and the file over `ORGANIZE_*` environment variables.
+pub fn parse<T: Parser>() -> T {
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    let matches = T::command().ignore_errors(true).get_matches_from(&argv);
+    let Some(path) = matches.get_one::<PathBuf>("config") else {
+        return T::parse_from(argv);
+    };
+
+    match config_args(&T::command(), &matches, path) {
+        Ok((extra, configured)) => {
+            // An environment variable would otherwise still apply where the file sets a switch to `false`
+            let command = configured
+                .iter()
+                .fold(T::command(), |command, id| command.mut_arg(id, |arg| arg.env(None)));
+            let matches = command.get_matches_from(argv.into_iter().chain(extra));
+            T::from_arg_matches(&matches).unwrap_or_else(|err| err.exit())
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Turns the entries of the config file at `path` into arguments for every option that
+/// `matches` didn't get from the command line, also returning the ids of those options.
+fn config_args(command: &Command, matches: &ArgMatches, path: &Path) -> Result<(Vec<OsString>, Vec<String>), String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read config {}: {}", path.display(), err))?;
+    let table: toml::Table = contents
+        .parse()
+        .map_err(|err| format!("Invalid config {}: {}", path.display(), err))?;
+
+    let mut args = Vec::new();
+    let mut configured = Vec::new();
+    for (key, value) in &table {
+        let id = key.replace('-', "_");
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == id.as_str() && id != "config")
+            .ok_or_else(|| format!("Unknown option `{}` in config {}", key, path.display()))?;
+        if matches.value_source(&id) == Some(ValueSource::CommandLine) {
+            continue;
+        }
+        configured.push(id.clone());
+
+        let values = match value {
+            toml::Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let text = match value {
+                toml::Value::String(text) => text.clone(),
+                toml::Value::Integer(number) => number.to_string(),
+                toml::Value::Float(number) => number.to_string(),
+                toml::Value::Boolean(flag) if matches!(arg.get_action(), ArgAction::SetTrue) => {
+                    if *flag {
+                        args.push(OsString::from(format!("--{}", arg.get_long().unwrap())));
+                    }
+                    continue;
+                }
+                value => return Err(format!("Unsupported value for `{}` in config {}: {}", key, path.display(), value)),
+            };
+            match arg.get_long() {
+                Some(long) => args.push(OsString::from(format!("--{}={}", long, text))),
+                None => args.push(OsString::from(text)),
+            }
+        }
+    }
+    Ok((args, configured))
+}
@@ -0,0 +1,83 @@
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use std::io;
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+
+/// Reads at most `limit` bytes from the start of a file.
+pub async fn read_prefix(path: &Path, limit: u64) -> io::Result<Vec<u8>> {
+    let file = File::open(path).await?;
+    let mut buffer = Vec::new();
+    file.take(limit).read_to_end(&mut buffer).await?;
+    Ok(buffer)
+}
+
+/// Treats any content containing a NUL byte as binary.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.contains(&0)
+}
+
+/// How many bytes `--mode language` reads from each file to detect its language.
+pub const LANGUAGE_SCAN_BYTES: u64 = 4 * 1024;
+
+/// ISO 639-3 codes reported by `whatlang`, paired with their two-letter ISO 639-1 codes.
+const LANGUAGE_CODES: &[(&str, &str)] = &[
+    ("afr", "af"), ("aka", "ak"), ("amh", "am"), ("ara", "ar"), ("aze", "az"), ("bel", "be"),
+    ("ben", "bn"), ("bul", "bg"), ("cat", "ca"), ("ces", "cs"), ("cmn", "zh"), ("cym", "cy"),
+    ("dan", "da"), ("deu", "de"), ("ell", "el"), ("eng", "en"), ("epo", "eo"), ("est", "et"),
+    ("fin", "fi"), ("fra", "fr"), ("guj", "gu"), ("heb", "he"), ("hin", "hi"), ("hrv", "hr"),
+    ("hun", "hu"), ("hye", "hy"), ("ind", "id"), ("ita", "it"), ("jav", "jv"), ("jpn", "ja"),
+    ("kan", "kn"), ("kat", "ka"), ("khm", "km"), ("kor", "ko"), ("lat", "la"), ("lav", "lv"),
+    ("lit", "lt"), ("mal", "ml"), ("mar", "mr"), ("mkd", "mk"), ("mya", "my"), ("nep", "ne"),
+    ("nld", "nl"), ("nob", "nb"), ("ori", "or"), ("pan", "pa"), ("pes", "fa"), ("pol", "pl"),
+    ("por", "pt"), ("ron", "ro"), ("rus", "ru"), ("sin", "si"), ("slk", "sk"), ("slv", "sl"),
+    ("sna", "sn"), ("spa", "es"), ("srp", "sr"), ("swe", "sv"), ("tam", "ta"), ("tel", "te"),
+    ("tgl", "tl"), ("tha", "th"), ("tuk", "tk"), ("tur", "tr"), ("ukr", "uk"), ("urd", "ur"),
+    ("uzb", "uz"), ("vie", "vi"), ("yid", "yi"), ("zul", "zu"),
+];
+
+/// Detects the language of text content, returning its two-letter code when detection is reliable.
+pub fn detect_language(bytes: &[u8]) -> Option<&'static str> {
+    if is_binary(bytes) {
+        return None;
+    }
+    let info = whatlang::detect(&String::from_utf8_lossy(bytes))?;
+    if !info.is_reliable() {
+        return None;
+    }
+    let code = info.lang().code();
+    let short = LANGUAGE_CODES.iter().find(|(long, _)| *long == code).map(|(_, short)| *short);
+    Some(short.unwrap_or(code))
+}
+
+/// How many bytes `--mode encoding` reads from each file to detect its encoding.
+pub const ENCODING_SCAN_BYTES: u64 = 64 * 1024;
+
+/// Detects the character encoding of text content, as a folder name such as `utf8`,
+/// `latin1`, `utf16` or `shift_jis`. Returns `None` for empty or binary content.
+///
+/// `truncated` says whether `bytes` is only a prefix of the file, so a multi-byte character
+/// cut off at the end doesn't rule out UTF-8.
+pub fn detect_encoding(bytes: &[u8], truncated: bool) -> Option<String> {
+    // UTF-16 text is full of NUL bytes, so its byte order mark has to be checked first
+    if bytes.starts_with(&[0xFF, 0xFE]) || bytes.starts_with(&[0xFE, 0xFF]) {
+        return Some("utf16".to_string());
+    }
+    if bytes.is_empty() || is_binary(bytes) {
+        return None;
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(_) => return Some("utf8".to_string()),
+        Err(err) if truncated && err.error_len().is_none() => return Some("utf8".to_string()),
+        Err(_) => {}
+    }
+
+    let mut detector = EncodingDetector::new(Iso2022JpDetection::Allow);
+    detector.feed(bytes, !truncated);
+    let name = match detector.guess(None, Utf8Detection::Deny).name() {
+        // Browsers, and so encoding_rs, read ISO-8859-1 as its superset windows-1252
+        "windows-1252" => "latin1".to_string(),
+        name => name.to_lowercase(),
+    };
+    Some(name)
+}
@@ -0,0 +1,151 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::fs::{self, File};
+use std::io::SeekFrom;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// Name of the folder byte-identical files are clustered into.
+pub const DUPLICATES_DIR: &str = "duplicates";
+
+/// Hashes the full contents of a file with BLAKE3.
+pub async fn file_hash(path: &Path) -> io::Result<blake3::Hash> {
+    let mut file = File::open(path).await?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize())
+}
+
+/// Content hashes of the files in a `--skip-if-in` archive, grouped by file size.
+pub type ArchiveIndex = HashMap<u64, HashSet<blake3::Hash>>;
+
+/// Indexes the files anywhere under `archive` by size and content hash.
+///
+/// Only archived files with one of the incoming `sizes` could match, so only they are hashed.
+/// Each file is hashed as a stream, so memory grows with the number of matches, not the archive.
+/// Empty files are left out. Stops early, with a partial index, once `cancelled` is set.
+pub async fn archive_index(archive: &Path, sizes: &HashSet<u64>, cancelled: &AtomicBool) -> io::Result<ArchiveIndex> {
+    let mut index = ArchiveIndex::new();
+    let mut pending = vec![archive.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        if cancelled.load(Ordering::Relaxed) {
+            break;
+        }
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+                continue;
+            }
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            if file_type.is_file() && metadata.len() > 0 && sizes.contains(&metadata.len()) {
+                if let Ok(hash) = file_hash(&entry.path()).await {
+                    index.entry(metadata.len()).or_default().insert(hash);
+                }
+            }
+        }
+    }
+    Ok(index)
+}
+
+/// Whether a byte-identical copy of `file`, which is `size` bytes long, is in the archive.
+pub async fn is_archived(file: &Path, size: u64, index: &ArchiveIndex) -> bool {
+    let Some(hashes) = index.get(&size) else {
+        return false;
+    };
+    file_hash(file).await.is_ok_and(|hash| hashes.contains(&hash))
+}
+
+/// Whether `other` holds the same bytes as `file`, which is `size` bytes long.
+pub async fn is_identical(file: &Path, size: u64, other: &Path) -> bool {
+    if !fs::metadata(other).await.is_ok_and(|metadata| metadata.is_file() && metadata.len() == size) {
+        return false;
+    }
+    match (file_hash(file).await, file_hash(other).await) {
+        (Ok(hash), Ok(other_hash)) => hash == other_hash,
+        _ => false,
+    }
+}
+
+/// Default number of bytes [`fingerprint`] reads from each end of a file.
+pub const DEFAULT_FINGERPRINT_WINDOW: u64 = 64 * 1024;
+
+/// Hashes the first and last `window` bytes of a file together with its size.
+///
+/// Cheap to compute for large files, and files with different fingerprints can't be identical.
+pub async fn fingerprint(path: &Path, size: u64, window: u64) -> io::Result<blake3::Hash> {
+    let mut file = File::open(path).await?;
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&size.to_le_bytes());
+
+    let mut head = Vec::new();
+    (&mut file).take(window).read_to_end(&mut head).await?;
+    hasher.update(&head);
+
+    if size > window {
+        file.seek(SeekFrom::Start(size.saturating_sub(window).max(window))).await?;
+        let mut tail = Vec::new();
+        file.take(window).read_to_end(&mut tail).await?;
+        hasher.update(&tail);
+    }
+    Ok(hasher.finalize())
+}
+
+/// Finds files with identical contents, mapping each of them to the `group-<hash>` folder it belongs in.
+///
+/// Files are grouped by size, then by a [`fingerprint`] of `window` bytes from each end, and
+/// only files whose fingerprints collide get fully hashed. Empty and unreadable files are left out.
+/// Stops hashing, with partial groups, once `cancelled` is set.
+pub async fn groups(files: &[PathBuf], window: u64, cancelled: &AtomicBool) -> HashMap<PathBuf, String> {
+    let mut by_size: HashMap<u64, Vec<&PathBuf>> = HashMap::new();
+    for file in files {
+        match fs::metadata(file).await {
+            Ok(metadata) if metadata.len() > 0 => by_size.entry(metadata.len()).or_default().push(file),
+            _ => {}
+        }
+    }
+
+    let mut by_fingerprint: HashMap<blake3::Hash, Vec<&PathBuf>> = HashMap::new();
+    for (size, candidates) in by_size.into_iter().filter(|(_, candidates)| candidates.len() > 1) {
+        for file in candidates {
+            if cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+            if let Ok(fingerprint) = fingerprint(file, size, window).await {
+                by_fingerprint.entry(fingerprint).or_default().push(file);
+            }
+        }
+    }
+
+    let mut by_hash: HashMap<blake3::Hash, Vec<&PathBuf>> = HashMap::new();
+    for candidates in by_fingerprint.into_values().filter(|candidates| candidates.len() > 1) {
+        for file in candidates {
+            if cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+            if let Ok(hash) = file_hash(file).await {
+                by_hash.entry(hash).or_default().push(file);
+            }
+        }
+    }
+
+    let mut groups = HashMap::new();
+    for (hash, copies) in by_hash.into_iter().filter(|(_, copies)| copies.len() > 1) {
+        let folder = format!("group-{}", &hash.to_hex()[..16]);
+        for file in copies {
+            groups.insert(file.clone(), folder.clone());
+        }
+    }
+    groups
+}
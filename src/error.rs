@@ -0,0 +1,120 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Why organizing failed or stopped: a filesystem operation with the path it failed on, an
+/// invalid option, or a safety check that refused to continue.
+#[derive(Debug)]
+pub enum OrganizeError {
+    ReadDir(PathBuf, io::Error),
+    Read(PathBuf, io::Error),
+    CreateDir(PathBuf, io::Error),
+    Metadata(PathBuf, io::Error),
+    Move { from: PathBuf, to: PathBuf, source: io::Error },
+    Copy { from: PathBuf, to: PathBuf, source: io::Error },
+    Remove(PathBuf, io::Error),
+    Write(PathBuf, io::Error),
+    OpenLog(PathBuf, io::Error),
+    /// A worker task panicked or was cancelled.
+    Task(tokio::task::JoinError),
+    /// Options that together exclude every file, like `--since` after `--until`.
+    EmptySelection(String),
+    Rules(String),
+    ExcludePattern(globset::Error),
+    AllowedDests(String),
+    GitHistory(String),
+    /// `--prescan-strict` found this many unreadable files.
+    Unreadable(usize),
+    /// More moves were planned than `--max-operations` allows.
+    LimitExceeded { planned: usize, limit: usize },
+    /// `--verify-after-run` found moved files that don't match the plan.
+    Verification { discrepancies: Vec<String>, moves: usize },
+    /// Files that could not be moved, each with its own error.
+    Failed(Vec<OrganizeError>),
+    /// The run was interrupted with Ctrl-C before every file was handled.
+    Interrupted,
+}
+
+impl OrganizeError {
+    /// The process exit code for this error: 130 for an interrupted run, as after SIGINT, and 1 otherwise.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            OrganizeError::Interrupted => 130,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for OrganizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrganizeError::ReadDir(path, err) => write!(f, "Failed to read directory {}: {}", path.display(), err),
+            OrganizeError::Read(path, err) => write!(f, "Failed to read {}: {}", path.display(), err),
+            OrganizeError::CreateDir(path, err) => write!(f, "Failed to create folder {}: {}", path.display(), err),
+            OrganizeError::Metadata(path, err) => write!(f, "Failed to read metadata of {}: {}", path.display(), err),
+            OrganizeError::Move { from, to, source } => {
+                write!(f, "Failed to move {} to {}: {}", from.display(), to.display(), source)
+            }
+            OrganizeError::Copy { from, to, source } => {
+                write!(f, "Failed to copy {} to {}: {}", from.display(), to.display(), source)
+            }
+            OrganizeError::Remove(path, err) => write!(f, "Failed to remove {}: {}", path.display(), err),
+            OrganizeError::Write(path, err) => write!(f, "Failed to write {}: {}", path.display(), err),
+            OrganizeError::OpenLog(path, err) => write!(f, "Failed to open log file {}: {}", path.display(), err),
+            OrganizeError::Task(err) => write!(f, "A worker task failed: {}", err),
+            OrganizeError::EmptySelection(reason) => write!(f, "{}, so no file could match", reason),
+            OrganizeError::Rules(message) | OrganizeError::AllowedDests(message) => write!(f, "{}", message),
+            OrganizeError::ExcludePattern(err) => write!(f, "Invalid --exclude pattern: {}", err),
+            OrganizeError::GitHistory(message) => write!(f, "Failed to read Git history: {}", message),
+            OrganizeError::Unreadable(count) => {
+                write!(f, "Refusing to organize: {} file(s) cannot be read and --prescan-strict is set", count)
+            }
+            OrganizeError::LimitExceeded { planned, limit } => write!(
+                f,
+                "Refusing to organize: {} moves planned, more than --max-operations {}. Use --force to proceed anyway.",
+                planned, limit
+            ),
+            OrganizeError::Verification { discrepancies, moves } => {
+                write!(f, "Verification failed: {} discrepancies in {} moves:", discrepancies.len(), moves)?;
+                for discrepancy in discrepancies {
+                    write!(f, "\n  {}", discrepancy)?;
+                }
+                Ok(())
+            }
+            OrganizeError::Failed(failures) => {
+                write!(f, "{} file(s) could not be moved:", failures.len())?;
+                for failure in failures {
+                    write!(f, "\n  {}", failure)?;
+                }
+                Ok(())
+            }
+            OrganizeError::Interrupted => write!(f, "Interrupted before every file was organized"),
+        }
+    }
+}
+
+impl std::error::Error for OrganizeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OrganizeError::ReadDir(_, err)
+            | OrganizeError::Read(_, err)
+            | OrganizeError::CreateDir(_, err)
+            | OrganizeError::Metadata(_, err)
+            | OrganizeError::Remove(_, err)
+            | OrganizeError::Write(_, err)
+            | OrganizeError::OpenLog(_, err) => Some(err),
+            OrganizeError::Move { source, .. } | OrganizeError::Copy { source, .. } => Some(source),
+            OrganizeError::Task(err) => Some(err),
+            OrganizeError::ExcludePattern(err) => Some(err),
+            OrganizeError::EmptySelection(_)
+            | OrganizeError::Rules(_)
+            | OrganizeError::AllowedDests(_)
+            | OrganizeError::GitHistory(_)
+            | OrganizeError::Unreadable(_)
+            | OrganizeError::LimitExceeded { .. }
+            | OrganizeError::Verification { .. }
+            | OrganizeError::Failed(_)
+            | OrganizeError::Interrupted => None,
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use exif::{Exif, In, Tag, Value};
+use organize::category::{self, Category};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Reads the `DateTimeOriginal` EXIF tag of an image, the moment the photo was taken.
+///
+/// The tag holds the camera's wall-clock time. When the `OffsetTimeOriginal` tag records the
+/// camera's UTC offset, the time is converted to UTC like every other timestamp, unless
+/// `keep_offset` asks for the wall-clock time where the photo was taken.
+///
+/// Returns `None` for files without an image extension and for images without the tag.
+pub fn capture_datetime(path: &Path, keep_offset: bool) -> Option<NaiveDateTime> {
+    let ext = path.extension()?.to_string_lossy();
+    if category::categorize(&ext) != Category::Images {
+        return None;
+    }
+
+    let file = File::open(path).ok()?;
+    let exif = exif::Reader::new().read_from_container(&mut BufReader::new(file)).ok()?;
+    let mut datetime = exif::DateTime::from_ascii(ascii_tag(&exif, Tag::DateTimeOriginal)?).ok()?;
+    let wall_clock = NaiveDate::from_ymd_opt(datetime.year.into(), datetime.month.into(), datetime.day.into())?
+        .and_hms_opt(datetime.hour.into(), datetime.minute.into(), datetime.second.into())?;
+    if keep_offset {
+        return Some(wall_clock);
+    }
+
+    if let Some(offset) = ascii_tag(&exif, Tag::OffsetTimeOriginal) {
+        let _ = datetime.parse_offset(offset);
+    }
+    match datetime.offset {
+        Some(minutes) => wall_clock.checked_sub_signed(Duration::minutes(minutes.into())),
+        None => Some(wall_clock),
+    }
+}
+
+/// Returns the first string of an ASCII tag in the primary image.
+fn ascii_tag(exif: &Exif, tag: Tag) -> Option<&[u8]> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => values.first().map(Vec::as_slice),
+        _ => None,
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Built-in aliases collapsing equivalent extensions onto one canonical spelling.
+pub const DEFAULT_ALIASES: &[(&str, &str)] = &[
+    ("jpeg", "jpg"),
+    ("jpe", "jpg"),
+    ("tiff", "tif"),
+    ("htm", "html"),
+    ("yml", "yaml"),
+    ("mpeg", "mpg"),
+];
+
+/// Maps extension aliases to their canonical extension.
+pub struct Aliases(HashMap<String, String>);
+
+impl Aliases {
+    /// Builds the alias table from the defaults, with `overrides` taking precedence.
+    pub fn new(overrides: &[(String, String)]) -> Self {
+        let mut aliases: HashMap<String, String> = DEFAULT_ALIASES
+            .iter()
+            .map(|(alias, canonical)| (alias.to_string(), canonical.to_string()))
+            .collect();
+        for (alias, canonical) in overrides {
+            aliases.insert(alias.to_lowercase(), canonical.to_lowercase());
+        }
+        Aliases(aliases)
+    }
+
+    /// Returns the lowercase canonical extension of a file, if it has one.
+    pub fn normalized_extension(&self, file_path: &Path) -> Option<String> {
+        let extension = file_path.extension()?.to_str()?.to_lowercase();
+        Some(self.0.get(&extension).cloned().unwrap_or(extension))
+    }
+
+    /// Returns the file name with an aliased extension replaced by its canonical one.
+    pub fn normalized_file_name(&self, file_path: &Path) -> Option<String> {
+        let extension = file_path.extension()?.to_str()?;
+        let canonical = self.0.get(&extension.to_lowercase())?;
+        let stem = file_path.file_stem()?.to_str()?;
+        Some(format!("{}.{}", stem, canonical))
+    }
+}
+
+/// Parses an `alias=canonical` pair given to `--ext-alias`.
+pub fn parse_alias(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((alias, canonical)) if !alias.is_empty() && !canonical.is_empty() => {
+            Ok((alias.trim_start_matches('.').to_string(), canonical.trim_start_matches('.').to_string()))
+        }
+        _ => Err(format!("expected `alias=canonical`, found `{}`", value)),
+    }
+}
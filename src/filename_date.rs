@@ -0,0 +1,54 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use regex::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// Common phone, camera and screenshot naming schemes, tried in order.
+static BUILT_IN_PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    [
+        // IMG_20240115_103000.jpg, Screenshot_20240115-103000.png
+        r"(?:^|\D)(?P<y>\d{4})(?P<m>\d{2})(?P<d>\d{2})[_-](?P<H>\d{2})(?P<M>\d{2})(?P<S>\d{2})(?:\D|$)",
+        // Screenshot 2024-01-15 at 10.30.00.png, 2024-01-15 10-30-00.mp4
+        r"(?:^|\D)(?P<y>\d{4})-(?P<m>\d{2})-(?P<d>\d{2})[ _T]+(?:at )?(?P<H>\d{2})[.:-](?P<M>\d{2})[.:-](?P<S>\d{2})(?:\D|$)",
+        // scan-2024-01-15.pdf
+        r"(?:^|\D)(?P<y>\d{4})-(?P<m>\d{2})-(?P<d>\d{2})(?:\D|$)",
+        // PXL_20240115.jpg
+        r"(?:^|\D)(?P<y>\d{4})(?P<m>\d{2})(?P<d>\d{2})(?:\D|$)",
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).unwrap())
+    .collect()
+});
+
+/// Parses a `--filename-pattern` regex, which must capture `y`, `m` and `d` groups.
+pub fn parse_pattern(value: &str) -> Result<Regex, String> {
+    let pattern = Regex::new(value).map_err(|err| err.to_string())?;
+    let names: Vec<&str> = pattern.capture_names().flatten().collect();
+    for group in ["y", "m", "d"] {
+        if !names.contains(&group) {
+            return Err(format!("pattern must have a named `(?P<{}>...)` group", group));
+        }
+    }
+    Ok(pattern)
+}
+
+/// Extracts a timestamp from the file name, trying `custom` patterns before the built-in ones.
+pub fn parse(file_path: &Path, custom: &[Regex]) -> Option<NaiveDateTime> {
+    let file_name = file_path.file_name()?.to_str()?;
+    custom
+        .iter()
+        .chain(BUILT_IN_PATTERNS.iter())
+        .find_map(|pattern| from_captures(pattern, file_name))
+}
+
+fn from_captures(pattern: &Regex, file_name: &str) -> Option<NaiveDateTime> {
+    let captures = pattern.captures(file_name)?;
+    let number = |group: &str| captures.name(group).and_then(|value| value.as_str().parse::<u32>().ok());
+
+    let date = NaiveDate::from_ymd_opt(number("y")? as i32, number("m")?, number("d")?)?;
+    date.and_hms_opt(
+        number("H").unwrap_or(0),
+        number("M").unwrap_or(0),
+        number("S").unwrap_or(0),
+    )
+}
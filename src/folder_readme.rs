@@ -0,0 +1,42 @@
+use chrono::NaiveDateTime;
+use std::io::{self, Read};
+use std::path::Path;
+use tokio::fs;
+
+/// How every folder README starts, which tells it apart from a file of the user's with the same name.
+const HEADER: &str = "This folder was organized by organize.\n";
+
+/// Whether the file at `path` is a folder README written by [`write`].
+pub fn is_folder_readme(path: &Path) -> bool {
+    let mut start = [0; HEADER.len()];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut start))
+        .is_ok_and(|()| start == HEADER.as_bytes())
+}
+
+/// Writes `file_name` into `folder`, describing the files moved there and the dates they span.
+///
+/// Returns `Ok(false)` when the file already exists and `overwrite` is off.
+pub async fn write(
+    folder: &Path,
+    file_name: &str,
+    count: usize,
+    dates: &[NaiveDateTime],
+    overwrite: bool,
+) -> io::Result<bool> {
+    let path = folder.join(file_name);
+    if !overwrite && fs::try_exists(&path).await? {
+        return Ok(false);
+    }
+
+    let mut contents = format!("{}\nFiles: {}\n", HEADER, count);
+    if let (Some(first), Some(last)) = (dates.iter().min(), dates.iter().max()) {
+        contents.push_str(&format!(
+            "Date range: {} to {}\n",
+            first.format("%Y-%m-%d"),
+            last.format("%Y-%m-%d")
+        ));
+    }
+    fs::write(&path, contents).await?;
+    Ok(true)
+}
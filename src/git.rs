@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Maps every file touched in the history of the repository containing `dir`
+/// to the author of the most recent commit that changed it.
+#[cfg(feature = "git")]
+pub fn last_authors(dir: &Path) -> Result<HashMap<PathBuf, String>, String> {
+    use git2::{Repository, Sort};
+
+    let repo = Repository::discover(dir).map_err(|err| err.message().to_string())?;
+    let workdir = repo
+        .workdir()
+        .ok_or("Git repository has no working directory")?
+        .canonicalize()
+        .map_err(|err| err.to_string())?;
+
+    let mut revwalk = repo.revwalk().map_err(|err| err.message().to_string())?;
+    revwalk.push_head().map_err(|err| err.message().to_string())?;
+    revwalk.set_sorting(Sort::TIME).map_err(|err| err.message().to_string())?;
+
+    let mut authors = HashMap::new();
+    for oid in revwalk {
+        let commit = oid
+            .and_then(|oid| repo.find_commit(oid))
+            .map_err(|err| err.message().to_string())?;
+        let tree = commit.tree().map_err(|err| err.message().to_string())?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree().map_err(|err| err.message().to_string())?),
+            Err(_) => None,
+        };
+        let diff = repo
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+            .map_err(|err| err.message().to_string())?;
+
+        let author = commit.author().name().unwrap_or("unknown").to_string();
+        for delta in diff.deltas() {
+            if let Some(path) = delta.new_file().path() {
+                authors.entry(workdir.join(path)).or_insert_with(|| author.clone());
+            }
+        }
+    }
+
+    Ok(authors)
+}
+
+#[cfg(not(feature = "git"))]
+pub fn last_authors(_dir: &Path) -> Result<HashMap<PathBuf, String>, String> {
+    Err("organize was built without the `git` feature".to_string())
+}
+
+/// Turns an author name into something safe to use as a folder name.
+pub fn author_folder(author: &str) -> String {
+    author.replace(['/', '\\'], "_")
+}
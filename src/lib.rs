@@ -0,0 +1,7 @@
+//! Planning building blocks that don't touch the filesystem, shared by the
+//! `organize` binary and its benchmarks.
+
+pub mod category;
+pub mod extension;
+pub mod planner;
+pub mod week;
@@ -0,0 +1,12 @@
+use std::time::Duration;
+
+/// How long to wait between load samples while paused for `--max-load`.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The 1-minute load average, or `None` if the system can't report it.
+pub fn one_minute() -> Option<f64> {
+    let mut loads = [0.0_f64; 1];
+    // SAFETY: getloadavg writes at most `nelem` samples, and the buffer holds one
+    let samples = unsafe { libc::getloadavg(loads.as_mut_ptr(), 1) };
+    (samples >= 1).then_some(loads[0])
+}
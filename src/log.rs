@@ -0,0 +1,85 @@
+use chrono::{Local, SecondsFormat};
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+/// Whether `path` is the log at `log_path` or its rotated copy, so runs don't organize their own log.
+pub fn is_log_file(path: &Path, log_path: Option<&Path>) -> bool {
+    let Some(log_path) = log_path else {
+        return false;
+    };
+    let Ok(path) = std::fs::canonicalize(path) else {
+        return false;
+    };
+
+    let mut rotated = log_path.as_os_str().to_os_string();
+    rotated.push(".1");
+    path == log_path || path.as_os_str() == rotated
+}
+
+/// A timestamped progress log, rotated to `<path>.1` once it grows past `max_bytes`.
+///
+/// Each line is written straight to the file, so `tail -f` follows the run and nothing is lost
+/// when a run stops early.
+pub struct LogFile {
+    path: PathBuf,
+    max_bytes: Option<u64>,
+    state: Mutex<State>,
+}
+
+struct State {
+    file: File,
+    written: u64,
+}
+
+impl LogFile {
+    pub async fn open(path: &Path, max_bytes: Option<u64>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path).await?;
+        let written = file.metadata().await?.len();
+        Ok(LogFile {
+            path: path.to_path_buf(),
+            max_bytes,
+            state: Mutex::new(State {
+                file,
+                written,
+            }),
+        })
+    }
+
+    /// Appends a timestamped line, rotating the file first if it would grow too large.
+    pub async fn write(&self, message: &str) {
+        let line = format!("{} {}\n", Local::now().to_rfc3339_opts(SecondsFormat::Secs, true), message);
+        let mut state = self.state.lock().await;
+
+        if let Some(max_bytes) = self.max_bytes {
+            if state.written > 0 && state.written + line.len() as u64 > max_bytes {
+                if let Err(err) = self.rotate(&mut state).await {
+                    eprintln!("Failed to rotate {}: {}", self.path.display(), err);
+                }
+            }
+        }
+
+        // tokio hands writes to a blocking thread, and only a flush waits for them to land
+        let written = match state.file.write_all(line.as_bytes()).await {
+            Ok(()) => state.file.flush().await,
+            Err(err) => Err(err),
+        };
+        match written {
+            Ok(()) => state.written += line.len() as u64,
+            Err(err) => eprintln!("Failed to write to {}: {}", self.path.display(), err),
+        }
+    }
+
+    async fn rotate(&self, state: &mut State) -> io::Result<()> {
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(".1");
+        fs::rename(&self.path, &rotated).await?;
+
+        let file = OpenOptions::new().create(true).append(true).open(&self.path).await?;
+        state.file = file;
+        state.written = 0;
+        Ok(())
+    }
+}
@@ -1,137 +1,3288 @@
-use clap::Parser;
+mod allowed_dests;
+mod color;
+mod config;
+mod content;
+mod duplicates;
+mod error;
+mod exif_date;
+mod filename_date;
+mod folder_readme;
+mod git;
+#[cfg(unix)]
+mod load;
+mod log;
+mod manifest;
+mod mirror;
+mod naming;
+mod output;
+mod plan_canonical;
+mod plan_dot;
+mod progress;
+mod rules;
+mod script;
+mod sidecar;
+mod summary;
+mod symlink;
+mod transfer;
+mod trash;
+
+use clap::{Parser, ValueEnum};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::Regex;
 use tokio::fs;
 use tokio::task::LocalSet;
-use tokio::sync::Mutex;
-use std::path::PathBuf;
-use chrono::{Datelike, NaiveDateTime, Local, Duration};
+use tokio::sync::{Mutex, Semaphore};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Local};
+use filetime::FileTime;
+use indicatif::ProgressBar;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::SystemTime;
+use organize::{category, extension};
+use allowed_dests::AllowedDests;
+use organize::extension::Aliases;
+use output::{describe_move, Output};
+use organize::planner::{date_folder, DateLayout, Granularity, Levels, Trace, WeekLabel};
+use organize::week::{WeekStart, WeekSystem};
+use error::OrganizeError;
+use log::LogFile;
+use progress::Progress;
+use rules::Rule;
+use summary::{Summary, SummaryFormat};
 
 #[derive(Parser)]
 #[clap(name = "organizer", about = "A file organizer tool")]
 struct Cli {
     /// The directory to organize
+    #[clap(env = "ORGANIZE_DIR")]
     dir: String,
+    /// Build the organized tree in this directory instead of inside the one being organized
+    #[clap(short, long, value_name = "DIR", env = "ORGANIZE_OUTPUT")]
+    output: Option<PathBuf>,
+    /// TOML file of default options, keyed by long flag name; flags on the command line win
+    #[clap(long, value_name = "PATH", env = "ORGANIZE_CONFIG")]
+    config: Option<PathBuf>,
     /// Reverse the organization
-    #[clap(short, long)]
+    #[clap(short, long, env = "ORGANIZE_REVERSE")]
     reverse: bool,
+    /// Group files into a folder named after the source path component at this depth
+    #[clap(long, value_name = "INDEX", env = "ORGANIZE_GROUP_BY_COMPONENT")]
+    group_by_component: Option<usize>,
+    /// Collate files sharing a name prefix up to this delimiter into one folder
+    #[clap(long, value_name = "DELIMITER", env = "ORGANIZE_COLLATE_PREFIX")]
+    collate_prefix: Option<String>,
+    /// CSV of `pattern,destination_template` rules; the first matching row wins
+    #[clap(long, value_name = "PATH", env = "ORGANIZE_RULES_CSV")]
+    rules_csv: Option<PathBuf>,
+    /// Abort before moving anything if more than this many moves are planned
+    #[clap(long, value_name = "N", env = "ORGANIZE_MAX_OPERATIONS")]
+    max_operations: Option<usize>,
+    /// Proceed even when a safety check would refuse to run
+    #[clap(long, env = "ORGANIZE_FORCE")]
+    force: bool,
+    /// How to label week folders
+    #[clap(long, value_enum, default_value_t = WeekLabel::Date, env = "ORGANIZE_WEEK_LABEL")]
+    week_label: WeekLabel,
+    /// Week numbering system used for week-number and ordinal week labels; US weeks start on --week-start
+    #[clap(long, value_enum, default_value_t = WeekSystem::Us, env = "ORGANIZE_WEEK_SYSTEM")]
+    week_system: WeekSystem,
+    /// Which day weeks start on, for `week of` folders and US ordinal and numbered weeks
+    #[clap(long, value_enum, default_value_t = WeekStart::Sunday, env = "ORGANIZE_WEEK_START")]
+    week_start: WeekStart,
+    /// strftime format for year folders, such as `%Y`
+    #[clap(long, value_name = "FORMAT", value_parser = parse_folder_format, env = "ORGANIZE_YEAR_FORMAT")]
+    year_format: Option<String>,
+    /// strftime format for month folders, such as `%m-%B`; defaults to the month's name
+    #[clap(long, value_name = "FORMAT", value_parser = parse_folder_format, env = "ORGANIZE_MONTH_FORMAT")]
+    month_format: Option<String>,
+    /// strftime format for week folders, applied to the week's first day, such as `%G-W%V`; overrides --week-label
+    #[clap(long, value_name = "FORMAT", value_parser = parse_folder_format, env = "ORGANIZE_WEEK_FORMAT")]
+    week_format: Option<String>,
+    /// What to group files by; files the mode can't place fall back to dates
+    #[clap(long, value_enum, default_value_t = Mode::Date, env = "ORGANIZE_MODE")]
+    mode: Mode,
+    /// How to render the closing summary
+    #[clap(long, value_enum, default_value_t = SummaryFormat::Human, env = "ORGANIZE_SUMMARY_FORMAT")]
+    summary_format: SummaryFormat,
+    /// Print nothing but errors
+    #[clap(short, long, conflicts_with_all = ["verbose", "explain", "count_only"], env = "ORGANIZE_QUIET")]
+    quiet: bool,
+    /// Print each move as it happens; repeat (`-vv`) to also print files left in place and why
+    #[clap(short, long, action = clap::ArgAction::Count, env = "ORGANIZE_VERBOSE")]
+    verbose: u8,
+    /// Treat extension ALIAS as CANONICAL, on top of the built-in aliases (e.g. `jpeg=jpg`)
+    #[clap(
+        long,
+        value_name = "ALIAS=CANONICAL",
+        value_parser = extension::parse_alias,
+        value_delimiter = ',',
+        env = "ORGANIZE_EXT_ALIAS"
+    )]
+    ext_alias: Vec<(String, String)>,
+    /// Rename files with an aliased extension to the canonical extension
+    #[clap(long, env = "ORGANIZE_NORMALIZE_EXT_NAMES")]
+    normalize_ext_names: bool,
+    /// Copy files through this staging directory on the destination filesystem before moving them into place
+    #[clap(long, value_name = "DIR", env = "ORGANIZE_STAGING")]
+    staging: Option<PathBuf>,
+    /// Only organize text files whose leading content matches this regex
+    #[clap(long, value_name = "REGEX", env = "ORGANIZE_CONTENT_MATCH")]
+    content_match: Option<Regex>,
+    /// How many bytes of each file `--content-match` reads
+    #[clap(long, value_name = "BYTES", default_value_t = 64 * 1024, env = "ORGANIZE_CONTENT_SCAN_BYTES")]
+    content_scan_bytes: u64,
+    /// Print how the destination of this one file is worked out, without moving anything
+    #[clap(long, value_name = "FILE", env = "ORGANIZE_EXPLAIN")]
+    explain: Option<PathBuf>,
+    /// Print how many files pass the filters and exit without planning or moving anything
+    #[clap(long, env = "ORGANIZE_COUNT_ONLY")]
+    count_only: bool,
+    /// Folder levels to create for dates
+    #[clap(long, value_enum, default_value_t = Levels::Nested, env = "ORGANIZE_LEVELS")]
+    levels: Levels,
+    /// How fine the nested date folders go
+    #[clap(long, value_enum, default_value_t = Granularity::Week, env = "ORGANIZE_GRANULARITY")]
+    granularity: Granularity,
+    /// Month (1-12) the fiscal year starts in; nested year folders are labelled by the fiscal year it starts
+    #[clap(
+        long,
+        value_name = "MONTH",
+        value_parser = clap::value_parser!(u32).range(1..=12),
+        env = "ORGANIZE_FISCAL_YEAR_START"
+    )]
+    fiscal_year_start: Option<u32>,
+    /// When reversing, set each file's modified time from the `week of` folder it was in
+    #[clap(long, env = "ORGANIZE_RESTORE_MTIME_FROM_FOLDER")]
+    restore_mtime_from_folder: bool,
+    /// When reversing, only flatten date folders and leave other folders, with their contents, intact.
+    /// A folder that already exists in the target directory is merged file by file, using `--on-conflict`
+    #[clap(long, requires = "reverse", env = "ORGANIZE_PRESERVE_RELATIVE_DEPTH")]
+    preserve_relative_depth: bool,
+    /// Set the modified time of each date folder to the date it represents
+    #[clap(long, env = "ORGANIZE_TOUCH_DESTINATION_DIRS")]
+    touch_destination_dirs: bool,
+    /// Append timestamped progress and error lines to this file
+    #[clap(long, value_name = "PATH", env = "ORGANIZE_LOG_FILE")]
+    log_file: Option<PathBuf>,
+    /// Rotate the log file to `<path>.1` once it grows past this many bytes
+    #[clap(long, value_name = "BYTES", requires = "log_file", env = "ORGANIZE_LOG_MAX_BYTES")]
+    log_max_bytes: Option<u64>,
+    /// After moving, check that every source is gone and every destination has the expected size
+    #[clap(long, env = "ORGANIZE_VERIFY_AFTER_RUN")]
+    verify_after_run: bool,
+    /// Split files into `high-volume/` and `low-volume/` by whether more than N files share their `--volume-key`
+    #[clap(long, value_name = "N", env = "ORGANIZE_VOLUME_THRESHOLD")]
+    volume_threshold: Option<usize>,
+    /// What files are grouped by when counting volume
+    #[clap(long, value_enum, default_value_t = VolumeKey::Extension, env = "ORGANIZE_VOLUME_KEY")]
+    volume_key: VolumeKey,
+    /// Sort files into `Images/`, `Videos/`, `Documents/`, `Audio/`, `Archives/` or `Other/` by extension first
+    #[clap(long, env = "ORGANIZE_BY_TYPE")]
+    by_type: bool,
+    /// Keep file names unique across the whole organized tree, suffixing any repeat like `file (1).txt`
+    #[clap(long, env = "ORGANIZE_RENAME_COLLISION_DETECT_GLOBAL")]
+    rename_collision_detect_global: bool,
+    /// Also organize the files of PATH into DIR/NAME, keeping track of which root they came from
+    #[clap(
+        long = "root",
+        value_name = "NAME=PATH",
+        value_parser = parse_root,
+        value_delimiter = ',',
+        env = "ORGANIZE_ROOT"
+    )]
+    roots: Vec<(String, PathBuf)>,
+    /// What to do with files whose timestamp can't be read
+    #[clap(long, value_enum, default_value_t = MissingTimestamp::Skip, env = "ORGANIZE_ON_MISSING_TIMESTAMP")]
+    on_missing_timestamp: MissingTimestamp,
+    /// Only organize files dated on or after this day (`YYYY-MM-DD`)
+    #[clap(long, value_name = "DATE", value_parser = parse_date, env = "ORGANIZE_SINCE")]
+    since: Option<NaiveDate>,
+    /// Only organize files dated on or before this day (`YYYY-MM-DD`)
+    #[clap(long, value_name = "DATE", value_parser = parse_date, env = "ORGANIZE_UNTIL")]
+    until: Option<NaiveDate>,
+    /// Regex whose first capture group extracts the number used by `--mode number`
+    #[clap(long, value_name = "REGEX", required_if_eq("mode", "number"), env = "ORGANIZE_NUMBER_REGEX")]
+    number_regex: Option<Regex>,
+    /// How many consecutive numbers share a folder in `--mode number`
+    #[clap(
+        long,
+        value_name = "N",
+        default_value_t = 1000,
+        value_parser = clap::value_parser!(u64).range(1..),
+        env = "ORGANIZE_BUCKET_SIZE"
+    )]
+    bucket_size: u64,
+    /// Where to take each file's date from, trying each source in order until one is available
+    #[clap(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [DateSource::Modified],
+        env = "ORGANIZE_DATE_SOURCES"
+    )]
+    date_sources: Vec<DateSource>,
+    /// Which file timestamp to date files by, falling back to the modified time where it's unavailable
+    #[clap(long, value_enum, conflicts_with = "date_sources", env = "ORGANIZE_TIME_SOURCE")]
+    time_source: Option<TimeSource>,
+    /// Date images by their EXIF capture time, falling back to the other date sources without one
+    #[clap(long, env = "ORGANIZE_USE_EXIF")]
+    use_exif: bool,
+    /// Keep EXIF capture times in the timezone the photo was taken in instead of converting them to UTC
+    #[clap(long, env = "ORGANIZE_USE_EXIF_OFFSET")]
+    use_exif_offset: bool,
+    /// Date each file `x` by the `timestamp` field of an `x.meta` JSON file next to it, where there is one
+    #[clap(long, env = "ORGANIZE_META_SIDECAR")]
+    meta_sidecar: bool,
+    /// Write a README into each destination folder listing its file count and date range
+    #[clap(long, env = "ORGANIZE_FOLDER_README")]
+    folder_readme: bool,
+    /// File name of the `--folder-readme` files
+    #[clap(
+        long,
+        value_name = "NAME",
+        default_value = "README.txt",
+        requires = "folder_readme",
+        env = "ORGANIZE_FOLDER_README_NAME"
+    )]
+    folder_readme_name: String,
+    /// Replace `--folder-readme` files left by earlier runs instead of keeping them
+    #[clap(long, requires = "folder_readme", env = "ORGANIZE_FOLDER_README_OVERWRITE")]
+    folder_readme_overwrite: bool,
+    /// Print the moves that would be made without touching the disk
+    #[clap(short = 'n', long, env = "ORGANIZE_DRY_RUN")]
+    dry_run: bool,
+    /// With --dry-run, only plan a stable pseudo-random FRACTION of the files, such as 0.01
+    #[clap(
+        long,
+        value_name = "FRACTION",
+        value_parser = parse_fraction,
+        requires = "dry_run",
+        env = "ORGANIZE_SAMPLE"
+    )]
+    sample: Option<f64>,
+    /// Copy files into the organized tree instead of moving them, keeping their modified times; files already copied are skipped
+    #[clap(long, env = "ORGANIZE_COPY")]
+    copy: bool,
+    /// Give files that failed to move one more attempt after every other file has been handled
+    #[clap(long, env = "ORGANIZE_RETRY_FAILED_PASS")]
+    retry_failed_pass: bool,
+    /// Move symlinks themselves into a `symlinks/` folder, keeping their targets valid
+    #[clap(long, env = "ORGANIZE_SYMLINKS_FOLDER")]
+    symlinks_folder: bool,
+    /// Organize symlinks to files like the files themselves, dated by their targets
+    #[clap(long, conflicts_with = "symlinks_folder", env = "ORGANIZE_FOLLOW_SYMLINKS")]
+    follow_symlinks: bool,
+    /// How many files to work on at once [default: number of CPUs]
+    #[clap(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        env = "ORGANIZE_JOBS"
+    )]
+    jobs: Option<u32>,
+    /// Stop starting new moves once this many have failed [default: unlimited]
+    #[clap(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..),
+        env = "ORGANIZE_MAX_ERRORS"
+    )]
+    max_errors: Option<u64>,
+    /// Pause starting new moves while the 1-minute load average is above this value
+    #[cfg(unix)]
+    #[clap(long, value_name = "LOAD", env = "ORGANIZE_MAX_LOAD")]
+    max_load: Option<f64>,
+    /// Leave files whose name matches this glob where they are (e.g. `*.part`)
+    #[clap(long, value_name = "GLOB", value_delimiter = ',', env = "ORGANIZE_EXCLUDE")]
+    exclude: Vec<Glob>,
+    /// Only organize files with this extension, case-insensitively; repeat for more (e.g. `jpg`)
+    #[clap(long, value_name = "EXT", value_delimiter = ',', env = "ORGANIZE_INCLUDE")]
+    include: Vec<String>,
+    /// Leave files smaller than this where they are (e.g. `500k`, `10MB`)
+    #[clap(long, value_name = "SIZE", value_parser = parse_size, env = "ORGANIZE_MIN_SIZE")]
+    min_size: Option<u64>,
+    /// Leave files larger than this where they are (e.g. `2GiB`)
+    #[clap(long, value_name = "SIZE", value_parser = parse_size, env = "ORGANIZE_MAX_SIZE")]
+    max_size: Option<u64>,
+    /// File listing the only destination folders files may be moved into, one per line
+    #[clap(long, value_name = "PATH", env = "ORGANIZE_ALLOWED_DESTS")]
+    allowed_dests: Option<PathBuf>,
+    /// Also organize files in subdirectories, into date folders at the top of the target directory
+    #[clap(long, env = "ORGANIZE_RECURSIVE")]
+    recursive: bool,
+    /// Place each file in the folder a same-named file has in this reference tree, or in `unmatched/`
+    #[clap(long, value_name = "REF_DIR", env = "ORGANIZE_MIRROR_STRUCTURE")]
+    mirror_structure: Option<PathBuf>,
+    /// With --dry-run, also write the planned folder tree as a Graphviz DOT file
+    #[clap(long, value_name = "PATH", requires = "dry_run", env = "ORGANIZE_PLAN_DOT")]
+    plan_dot: Option<PathBuf>,
+    /// Write every planned move, sorted by source, to this file before anything is moved
+    #[clap(long, value_name = "PATH", env = "ORGANIZE_PLAN_CANONICAL")]
+    plan_canonical: Option<PathBuf>,
+    /// Move byte-identical files together into `duplicates/group-<hash>/` for review
+    #[clap(long, env = "ORGANIZE_CLUSTER_DUPLICATES")]
+    cluster_duplicates: bool,
+    /// Bytes read from each end of a file to fingerprint it before a full duplicate hash
+    #[clap(
+        long,
+        value_name = "BYTES",
+        default_value_t = duplicates::DEFAULT_FINGERPRINT_WINDOW,
+        requires = "cluster_duplicates",
+        env = "ORGANIZE_FINGERPRINT_WINDOW"
+    )]
+    fingerprint_window: u64,
+    /// Leave files in place when a byte-identical copy is anywhere under this archive directory
+    #[clap(long, value_name = "ARCHIVE_DIR", env = "ORGANIZE_SKIP_IF_IN")]
+    skip_if_in: Option<PathBuf>,
+    /// With --skip-if-in, move already archived files to the trash instead of leaving them in place
+    #[clap(long, requires = "skip_if_in", env = "ORGANIZE_TRASH_ARCHIVED")]
+    trash_archived: bool,
+    /// Permanently delete files trashed by `--on-conflict` more than this many days ago
+    #[clap(long, value_name = "DAYS", env = "ORGANIZE_TRASH_RETENTION")]
+    trash_retention: Option<u32>,
+    /// Write every move performed to this JSON file, for `--undo`
+    #[clap(long, value_name = "PATH", env = "ORGANIZE_MANIFEST")]
+    manifest: Option<PathBuf>,
+    /// Move every file recorded in a `--manifest` back to where it came from
+    #[clap(
+        long,
+        value_name = "MANIFEST",
+        conflicts_with_all = ["reverse", "explain", "count_only"],
+        env = "ORGANIZE_UNDO"
+    )]
+    undo: Option<PathBuf>,
+    /// Only organize files with these extensions, leaving every other file in place (e.g. `jpg,png`)
+    #[clap(long, value_name = "EXT", value_delimiter = ',', env = "ORGANIZE_ONLY_EXT_DATED")]
+    only_ext_dated: Vec<String>,
+    /// What to do when a file already exists at the destination
+    #[clap(long, value_enum, default_value_t = ConflictPolicy::Rename, env = "ORGANIZE_ON_CONFLICT")]
+    on_conflict: ConflictPolicy,
+    /// Check that every file can be read before organizing and report the ones that can't
+    #[clap(long, env = "ORGANIZE_PRESCAN")]
+    prescan: bool,
+    /// Like `--prescan`, but abort if any file can't be read
+    #[clap(long, env = "ORGANIZE_PRESCAN_STRICT")]
+    prescan_strict: bool,
+    /// Minimum similarity (0 to 1) for taxonomy mode to route a file into an existing folder
+    #[clap(long, value_name = "SCORE", default_value_t = 0.7, env = "ORGANIZE_TAXONOMY_THRESHOLD")]
+    taxonomy_threshold: f64,
+    /// Write a shell script of the planned moves to this path instead of moving anything
+    #[clap(long, value_name = "PATH", env = "ORGANIZE_EMIT_SCRIPT")]
+    emit_script: Option<PathBuf>,
+    /// Extra regex for `--mode filename-date` with named `y`, `m`, `d` (and optional `H`, `M`, `S`) groups
+    #[clap(
+        long,
+        value_name = "REGEX",
+        value_parser = filename_date::parse_pattern,
+        env = "ORGANIZE_FILENAME_PATTERN"
+    )]
+    filename_pattern: Vec<Regex>,
+}
+
+impl Cli {
+    /// The `--jobs` limit, defaulting to the number of CPUs.
+    fn job_limit(&self) -> usize {
+        match self.jobs {
+            Some(jobs) => jobs as usize,
+            None => std::thread::available_parallelism().map_or(1, |count| count.get()),
+        }
+    }
+
+    /// The `--summary-format`, or no summary at all with `--quiet`.
+    fn summary_format(&self) -> SummaryFormat {
+        if self.quiet {
+            SummaryFormat::None
+        } else {
+            self.summary_format
+        }
+    }
+
+    /// Prints what a run stopped by Ctrl-C got done and returns the error it exits with.
+    fn interrupted(&self, summary: Summary) -> OrganizeError {
+        summary.print(None, self.summary_format());
+        OrganizeError::Interrupted
+    }
+
+    /// The date folder options, as the planner takes them.
+    fn date_layout(&self) -> DateLayout<'_> {
+        DateLayout {
+            levels: self.levels,
+            granularity: self.granularity,
+            week_label: self.week_label,
+            week_system: self.week_system,
+            week_start: self.week_start,
+            year_format: self.year_format.as_deref(),
+            month_format: self.month_format.as_deref(),
+            week_format: self.week_format.as_deref(),
+            fiscal_year_start: self.fiscal_year_start,
+        }
+    }
+
+    fn output(&self) -> Output {
+        Output::new(self.quiet, self.verbose)
+    }
+
+    /// A progress bar over `total` files, drawn only when stdout is a terminal and nothing
+    /// else is printed per file: not with `--quiet`, `--verbose` or `--dry-run`.
+    fn progress_bar(&self, total: u64) -> ProgressBar {
+        let visible = !self.quiet && self.verbose == 0 && !self.dry_run && std::io::stdout().is_terminal();
+        progress::bar(total, visible)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum VolumeKey {
+    /// The lowercase file extension
+    Extension,
+    /// The folder containing the file
+    Parent,
+}
+
+impl VolumeKey {
+    fn of(self, file_path: &Path) -> String {
+        match self {
+            VolumeKey::Extension => file_path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+            VolumeKey::Parent => file_path.parent().unwrap().to_string_lossy().into_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ConflictPolicy {
+    /// Move the incoming file under a free name such as `file (1).txt`
+    Rename,
+    /// Leave the incoming file where it is
+    Skip,
+    /// Replace the existing file
+    Overwrite,
+    /// Keep whichever file was modified most recently and trash the other
+    KeepNewest,
+    /// Keep whichever file is larger and trash the other
+    KeepLargest,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Mode {
+    /// Year, month and week folders
+    Date,
+    /// The last Git author of each file (requires the `git` feature)
+    GitAuthor,
+    /// The file's extension, with aliases collapsed
+    Extension,
+    /// How recently the file was accessed: `active`, `stale` or `cold`
+    Recency,
+    /// The existing subfolder whose name best matches the file name, or `unsorted`
+    Taxonomy,
+    /// Date folders using a timestamp embedded in the file name, such as `IMG_20240115_103000`
+    FilenameDate,
+    /// One folder per file category and year, such as `Documents-2024`
+    CategoryYear,
+    /// The detected language of text files, such as `en` or `de`
+    Language,
+    /// One of 256 `shard-00` to `shard-ff` folders, picked by a hash of the file's path
+    Shard,
+    /// Ranges of a number extracted from the file name by `--number-regex`, such as `0001`
+    Number,
+    /// The dominant color of images, such as `red` or `grayscale`
+    Color,
+    /// The character encoding of text files, such as `utf8`, `latin1` or `utf16`
+    Encoding,
+}
+
+impl Mode {
+    fn name(self) -> String {
+        self.to_possible_value().unwrap().get_name().to_string()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum DateSource {
+    /// A timestamp embedded in the file name, as matched by `--filename-pattern`
+    Filename,
+    /// When an image was taken, from its EXIF `DateTimeOriginal` tag
+    Exif,
+    /// The `timestamp` field of a `.meta` JSON file next to the file
+    Sidecar,
+    /// When the file was created, on platforms that record it
+    Created,
+    /// When the file was last modified
+    Modified,
+    /// When the file was last accessed
+    Accessed,
+}
+
+impl DateSource {
+    fn name(self) -> String {
+        self.to_possible_value().unwrap().get_name().to_string()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum TimeSource {
+    /// When the file was last modified
+    Modified,
+    /// When the file was created
+    Created,
+    /// When the file was last accessed
+    Accessed,
+}
+
+impl From<TimeSource> for DateSource {
+    fn from(source: TimeSource) -> Self {
+        match source {
+            TimeSource::Modified => DateSource::Modified,
+            TimeSource::Created => DateSource::Created,
+            TimeSource::Accessed => DateSource::Accessed,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum MissingTimestamp {
+    /// Leave the file where it is
+    Skip,
+    /// Move the file into an `undated` folder
+    Undated,
+    /// Date the file as if it was modified now
+    Now,
+}
+
+/// Shared state handed to every file task of an organize run.
+struct Context {
+    args: Cli,
+    root: PathBuf,
+    /// Where the organized tree is built: the `--output` directory, or `root` itself.
+    destination_root: PathBuf,
+    /// Prefixes shared by more than one file when collating by prefix.
+    collated_prefixes: HashSet<String>,
+    /// Number of files sharing each `--volume-key` value.
+    volume_counts: HashMap<String, usize>,
+    filters: Filters,
+    /// Last Git author of each tracked file, keyed by canonical path.
+    authors: HashMap<PathBuf, String>,
+    aliases: Aliases,
+    /// Set once the unreliable access time warning has been printed.
+    atime_warned: AtomicBool,
+    /// Set once the `--time-source` fallback warning has been printed.
+    time_source_warned: AtomicBool,
+    /// Where this run sets aside files that lose a conflict.
+    trash_folder: PathBuf,
+    /// Names of the subfolders that already exist in the target directory.
+    taxonomy: Vec<String>,
+    /// Set when the run is interrupted so moves that haven't started are skipped.
+    cancelled: Arc<AtomicBool>,
+    log: Option<LogFile>,
+    /// File names already used anywhere in the organized tree, for global collision detection.
+    taken_names: std::sync::Mutex<naming::TakenNames>,
+    /// Name of the `--root` each file from an extra root was scanned from.
+    sources: HashMap<PathBuf, String>,
+    progress: Progress,
+    /// Held by the task sampling the load average for `--max-load`, so the others queue behind it.
+    #[cfg(unix)]
+    load_gate: Mutex<()>,
+    /// The `group-<hash>` folder of each file with byte-identical copies, for `--cluster-duplicates`.
+    duplicate_groups: HashMap<PathBuf, String>,
+    /// Hashes of the files in the `--skip-if-in` archive.
+    archive: Option<duplicates::ArchiveIndex>,
+    /// Destinations already handed out this run, so renamed files never pick the same free name.
+    ///
+    /// Maps each destination to the file it was claimed for, so a retried file gets its own name back.
+    claimed_destinations: std::sync::Mutex<HashMap<PathBuf, PathBuf>>,
+    /// One lock per destination, held from checking for a conflict until the file is in place, so
+    /// files heading to the same path are compared and moved one at a time.
+    destination_locks: std::sync::Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>,
+    /// Limits how many files are planned or moved at once.
+    permits: Semaphore,
+    allowed_dests: Option<AllowedDests>,
+    /// The folder of each file name in the `--mirror-structure` reference tree.
+    mirror: Option<HashMap<OsString, PathBuf>>,
+    /// Files left in place because their destination is not in `--allowed-dests`, with that destination.
+    rejected: std::sync::Mutex<Vec<(PathBuf, PathBuf)>>,
+}
+
+impl Context {
+    /// Whether `--max-errors` moves have failed, so no new moves should start.
+    fn too_many_errors(&self) -> bool {
+        self.args.max_errors.is_some_and(|max| self.progress.errors() as u64 >= max)
+    }
+
+    /// Waits while the load average is above `--max-load`, sampling it every few seconds.
+    #[cfg(unix)]
+    async fn wait_for_low_load(&self) {
+        let Some(max_load) = self.args.max_load else {
+            return;
+        };
+        let _gate = self.load_gate.lock().await;
+        let mut paused = false;
+        while let Some(load) = load::one_minute().filter(|load| *load > max_load) {
+            if self.cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+            if !paused {
+                paused = true;
+                let message = format!("paused: load average {:.2} is above --max-load {}", load, max_load);
+                self.args.output().info(|| message.clone());
+                self.log(|| message).await;
+            }
+            tokio::time::sleep(load::POLL_INTERVAL).await;
+        }
+        if paused {
+            self.args.output().info(|| "resumed: load average is back under --max-load".to_string());
+            self.log(|| "resumed".to_string()).await;
+        }
+    }
+
+    /// Writes a line to the `--log-file`, if one was given.
+    async fn log(&self, message: impl FnOnce() -> String) {
+        if let Some(log) = &self.log {
+            log.write(&message()).await;
+        }
+    }
+}
+
+/// A move computed while planning, before anything touches the disk.
+#[derive(Clone)]
+struct PlannedMove {
+    from: PathBuf,
+    to: PathBuf,
+    bytes: u64,
+    /// The date the file was organized by, if it has one.
+    datetime: Option<NaiveDateTime>,
+    /// Date folders on the way to `to`, with the date each one stands for.
+    folder_dates: Vec<(PathBuf, NaiveDate)>,
+}
+
+impl PlannedMove {
+    fn into_record(self) -> MoveRecord {
+        MoveRecord {
+            from: self.from,
+            to: self.to,
+            bytes: self.bytes,
+            datetime: self.datetime,
+        }
+    }
+}
+
+/// A move that was carried out.
+struct MoveRecord {
+    from: PathBuf,
+    to: PathBuf,
+    bytes: u64,
+    datetime: Option<NaiveDateTime>,
+}
+
+impl MoveRecord {
+    /// The year of the date the file was organized by, if it has one.
+    fn year(&self) -> Option<i32> {
+        self.datetime.map(|datetime| datetime.year())
+    }
+
+    fn describe(&self, copied: bool) -> String {
+        describe_move(if copied { "copied" } else { "moved" }, &self.from, &self.to)
+    }
 }
 
 #[tokio::main]
 async fn main() {
     let local_set = LocalSet::new();
-    let args = Cli::parse();
+    let args: Cli = config::parse();
+
+    if !args.force {
+        // Every directory files are taken from or moved into, so neither --root nor --output gets around the check
+        let dirs = std::iter::once(Path::new(&args.dir))
+            .chain(args.roots.iter().map(|(_, root)| root.as_path()))
+            .chain(args.output.as_deref());
+        for dir in dirs {
+            if let Some(reason) = sensitive_directory(dir) {
+                eprintln!(
+                    "Refusing to organize {}: it is {}. Organizing it would scatter files the system or your account depend on. Use --force if you really mean it.",
+                    dir.display(), reason
+                );
+                std::process::exit(1);
+            }
+        }
+    }
 
-    local_set.run_until(async {
+    let result = local_set.run_until(async {
         if args.reverse {
-            reverse_organize(&args.dir).await;
+            let permits = Arc::new(Semaphore::new(args.job_limit()));
+            let options = ReverseOptions {
+                restore_mtime: args.restore_mtime_from_folder,
+                dry_run: args.dry_run,
+                preserve_user_folders: args.preserve_relative_depth,
+                on_conflict: args.on_conflict,
+            };
+            let bar = args.progress_bar(0);
+            let cancelled = Arc::new(AtomicBool::new(false));
+            let interrupt_listener = listen_for_interrupt(Arc::clone(&cancelled));
+            let readme_name = args.folder_readme_name.clone();
+            let (mut summary, failures) =
+                reverse_organize(&args.dir, options, permits, Arc::clone(&cancelled), readme_name, args.output(), bar.clone())
+                    .await?;
+            interrupt_listener.abort();
+            bar.finish_and_clear();
+            summary.completed = !cancelled.load(Ordering::Relaxed);
+            summary.print(None, args.summary_format());
+            if !failures.is_empty() {
+                return Err(OrganizeError::Failed(failures));
+            }
+            if !summary.completed {
+                return Err(OrganizeError::Interrupted);
+            }
+            Ok(())
         } else {
-            organize(&args.dir).await;
+            organize(args).await
         }
     }).await;
 
-    println!("Operation complete!");
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(err.exit_code());
+    }
 }
 
-async fn organize(dir: &str) {
-    let mut entries = fs::read_dir(dir).await.expect("Failed to read directory");
+/// Well-known system directories that should never be organized by accident.
+const SYSTEM_DIRS: &[&str] = &[
+    "/bin", "/boot", "/dev", "/etc", "/lib", "/lib64", "/opt", "/proc", "/sbin", "/sys", "/usr", "/var",
+    "/System", "/Library", "/Applications", "C:\\Windows", "C:\\Program Files",
+];
 
-    let mut tasks = Vec::new();
+/// Describes why `dir` is too sensitive to organize, if it is.
+fn sensitive_directory(dir: &Path) -> Option<&'static str> {
+    let dir = std::fs::canonicalize(dir).ok()?;
 
-    while let Some(entry) = entries.next_entry().await.expect("Failed to read entry") {
-        let path = entry.path();
-        if path.is_file() {
-            let task = tokio::task::spawn_local(async move {
-                organize_file(path).await;
-            });
-            tasks.push(task);
+    if dir.parent().is_none() {
+        return Some("the filesystem root");
+    }
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    if let Some(home) = home.and_then(|home| std::fs::canonicalize(home).ok()) {
+        if dir == home {
+            return Some("your home directory");
         }
     }
-
-    for task in tasks {
-        task.await.expect("Task failed");
+    if SYSTEM_DIRS.iter().any(|system_dir| dir == Path::new(system_dir)) {
+        return Some("a system directory");
     }
+    None
 }
 
-async fn organize_file(file_path: PathBuf) {
-    if let Ok(metadata) = fs::metadata(&file_path).await {
-        if let Ok(modified) = metadata.modified() {
-            #[allow(deprecated)]
-            let datetime = NaiveDateTime::from_timestamp(
-                modified.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() as i64,
-                0,
-            );
-            let year = datetime.year();
-            let month = datetime.month();
+/// What [`Filters::should_process`] decided for a file.
+enum Decision<'a> {
+    /// Organize the file, along the given route.
+    Process(Route<'a>),
+    /// Leave the file in place, for the given reason.
+    Skip(String),
+}
 
-            // Calculate the previous Sunday
-            let weekday = datetime.weekday().num_days_from_sunday();
-            let previous_sunday = datetime - Duration::days(weekday.into());
+/// How a file that passed the filters picks its destination.
+enum Route<'a> {
+    /// The first `--rules-csv` row whose pattern matches the file name.
+    Rule(&'a Rule),
+    /// The `--mode`, when no rule matches.
+    Mode(Mode),
+}
 
-            #[allow(deprecated)]
-            let month_name = chrono::TimeZone::ymd(&Local, year, month, 1).format("%B").to_string();
-            let week_folder_name = format!("week of {}", previous_sunday.format("%Y-%m-%d"));
+/// The selection filters and routing rules of a run.
+struct Filters {
+    excludes: GlobSet,
+    include: Vec<String>,
+    only_ext_dated: Vec<String>,
+    rules: Vec<Rule>,
+    mode: Mode,
+}
 
-            let year_folder = file_path.parent().unwrap().join(format!("{}", year));
-            let month_folder = year_folder.join(month_name);
-            let week_folder = month_folder.join(week_folder_name);
+impl Filters {
+    fn new(args: &Cli, rules: Vec<Rule>) -> Result<Self, globset::Error> {
+        let mut excludes = GlobSetBuilder::new();
+        for pattern in &args.exclude {
+            excludes.add(pattern.clone());
+        }
+        Ok(Filters {
+            excludes: excludes.build()?,
+            include: args.include.clone(),
+            only_ext_dated: args.only_ext_dated.clone(),
+            rules,
+            mode: args.mode,
+        })
+    }
 
-            fs::create_dir_all(&week_folder).await.expect("Failed to create folder");
+    /// Decides whether a file is organized, and how.
+    ///
+    /// A file goes through these checks in order, and the first one that applies wins:
+    ///
+    /// 1. `--exclude`: a matching file is left in place, even if it is also included or matches a rule.
+    /// 2. `--include`: a file whose extension isn't listed is left in place.
+    /// 3. `--only-ext-dated`: likewise, checked after `--include`.
+    /// 4. `--min-size` and `--max-size`: a file outside the size range is left in place.
+    /// 5. `--since` and `--until`: a file dated outside the range is left in place.
+    /// 6. `--content-match`: a text file whose content doesn't match is left in place.
+    /// 7. `--rules-csv`: the first matching rule picks the destination.
+    /// 8. `--mode` picks the destination of every other file.
+    ///
+    /// This runs the checks that only need the path, 1 to 3, and picks the route from 7 and 8.
+    /// Checks 4 to 6 need the file's metadata or contents and are run afterwards by [`filter_file`],
+    /// so rules only route files and never bring back a file a filter left out.
+    fn should_process(&self, file_path: &Path) -> Decision<'_> {
+        let Some(file_name) = file_path.file_name() else {
+            return Decision::Skip("no file name".to_string());
+        };
+        let ext = || file_path.extension().map(|ext| ext.to_string_lossy().to_lowercase()).unwrap_or_default();
 
-            let new_file_path = week_folder.join(file_path.file_name().unwrap());
-            fs::rename(&file_path, &new_file_path).await.expect("Failed to move file");
+        if self.excludes.is_match(file_name) {
+            return Decision::Skip("matches --exclude".to_string());
+        }
+        if !self.include.is_empty() && !has_listed_extension(file_path, &self.include) {
+            return Decision::Skip(format!("extension `{}` is not in --include", ext()));
+        }
+        if !self.only_ext_dated.is_empty() && !has_listed_extension(file_path, &self.only_ext_dated) {
+            return Decision::Skip(format!("extension `{}` is not in --only-ext-dated", ext()));
+        }
+        match rules::find(&self.rules, Path::new(file_name)) {
+            Some(rule) => Decision::Process(Route::Rule(rule)),
+            None => Decision::Process(Route::Mode(self.mode)),
         }
     }
 }
 
-async fn reverse_organize(dir: &str) {
-    let dir_clone = Arc::new(Mutex::new(dir.to_string()));
-    let mut tasks = Vec::new();
+/// Names of the visible subfolders of `dir`, or none if it can't be read.
+fn subfolder_names(dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| !name.starts_with('.'))
+        .collect()
+}
 
-    let mut entries = fs::read_dir(dir).await.expect("Failed to read directory");
+/// Why a symlink found while scanning is left in place, or `None` to organize it like a file.
+///
+/// Links are only organized with `--follow-symlinks`, and then only when their target exists.
+fn skipped_link_reason(link: &Path, follow: bool) -> Option<&'static str> {
+    if !follow {
+        Some("symlink, use --follow-symlinks to organize it")
+    } else if !link.exists() {
+        Some("broken symlink")
+    } else {
+        None
+    }
+}
 
-    while let Some(entry) = entries.next_entry().await.expect("Failed to read entry") {
-        let path = entry.path();
-        if path.is_dir() {
-            let dir_clone = Arc::clone(&dir_clone);
-            let task = tokio::task::spawn_local(async move {
-                reverse_organize_dir(path, dir_clone).await;
+/// Parses a `--root` value of the form `name=path`.
+fn parse_root(value: &str) -> Result<(String, PathBuf), String> {
+    match value.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() && !name.contains(['/', '\\']) => {
+            Ok((name.to_string(), PathBuf::from(path)))
+        }
+        _ => Err(format!("expected `name=path`, found `{}`", value)),
+    }
+}
+
+async fn organize(args: Cli) -> Result<(), OrganizeError> {
+    if let (Some(since), Some(until)) = (args.since, args.until) {
+        if since > until {
+            return Err(OrganizeError::EmptySelection(format!("--since {} is after --until {}", since, until)));
+        }
+    }
+    if let (Some(min_size), Some(max_size)) = (args.min_size, args.max_size) {
+        if min_size > max_size {
+            return Err(OrganizeError::EmptySelection(format!(
+                "--min-size {} is larger than --max-size {}",
+                min_size, max_size
+            )));
+        }
+    }
+
+    // Listening from the start means Ctrl-C during the scan, hashing or trash expiry stops the
+    // run between steps instead of killing it halfway through one
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let interrupt_listener = listen_for_interrupt(Arc::clone(&cancelled));
+
+    let rules = match &args.rules_csv {
+        Some(path) => rules::load_csv(path).map_err(OrganizeError::Rules)?,
+        None => Vec::new(),
+    };
+
+    let filters = Filters::new(&args, rules).map_err(OrganizeError::ExcludePattern)?;
+
+    let mirror = args
+        .mirror_structure
+        .as_ref()
+        .map(|reference| mirror::index(reference).map_err(|err| OrganizeError::ReadDir(reference.clone(), err)))
+        .transpose()?;
+    let root = PathBuf::from(&args.dir);
+    let destination_root = args.output.clone().unwrap_or_else(|| root.clone());
+    let allowed_dests = args
+        .allowed_dests
+        .as_ref()
+        .map(|path| AllowedDests::load(path, &destination_root).map_err(OrganizeError::AllowedDests))
+        .transpose()?;
+    let log_path = args.log_file.as_ref().and_then(|path| std::fs::canonicalize(path).ok());
+
+    let mut files = Vec::new();
+    let mut taxonomy = Vec::new();
+    let mut symlinks = Vec::new();
+    let mut skipped_links = Vec::new();
+
+    // Undoing moves the files listed in the manifest, so the directory isn't scanned.
+    let mut pending = if args.undo.is_some() { Vec::new() } else { vec![root.clone()] };
+    while let Some(dir) = pending.pop() {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err(args.interrupted(Summary::default()));
+        }
+        let read_error = |err| OrganizeError::ReadDir(dir.clone(), err);
+        let mut entries = fs::read_dir(&dir).await.map_err(read_error)?;
+        while let Some(entry) = entries.next_entry().await.map_err(read_error)? {
+            let path = entry.path();
+            let is_link = fs::symlink_metadata(&path).await.is_ok_and(|metadata| metadata.is_symlink());
+            if is_link && args.symlinks_folder {
+                symlinks.push(path);
+                continue;
+            }
+            if let Some(reason) = is_link.then(|| skipped_link_reason(&path, args.follow_symlinks)).flatten() {
+                skipped_links.push((path, reason));
+                continue;
+            }
+            if path.is_file()
+                && entry.file_name() != summary::STATS_FILE
+                && !log::is_log_file(&path, log_path.as_deref())
+                && !is_folder_readme(&path, &args.folder_readme_name)
+            {
+                files.push(path);
+            } else if path.is_dir() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if name.starts_with('.') {
+                    continue;
+                }
+                if dir == root {
+                    taxonomy.push(name);
+                }
+                if args.recursive && !is_link && path != destination_root {
+                    pending.push(path);
+                }
+            }
+        }
+    }
+
+    if args.output.is_some() {
+        taxonomy = subfolder_names(&destination_root);
+    }
+
+    let mut sources = HashMap::new();
+    for (name, source_root) in &args.roots {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err(args.interrupted(Summary::default()));
+        }
+        let read_error = |err| OrganizeError::ReadDir(source_root.clone(), err);
+        let mut entries = fs::read_dir(source_root).await.map_err(read_error)?;
+        while let Some(entry) = entries.next_entry().await.map_err(read_error)? {
+            let path = entry.path();
+            let is_link = fs::symlink_metadata(&path).await.is_ok_and(|metadata| metadata.is_symlink());
+            if let Some(reason) = is_link.then(|| skipped_link_reason(&path, args.follow_symlinks)).flatten() {
+                skipped_links.push((path, reason));
+                continue;
+            }
+            if path.is_file()
+                && entry.file_name() != summary::STATS_FILE
+                && !log::is_log_file(&path, log_path.as_deref())
+                && !is_folder_readme(&path, &args.folder_readme_name)
+            {
+                sources.insert(path.clone(), name.clone());
+                files.push(path);
+            }
+        }
+    }
+
+    // A sidecar goes wherever the file it describes goes, so it isn't planned on its own
+    let scanned: HashSet<PathBuf> = files.iter().cloned().collect();
+    let (sidecars, mut files): (Vec<PathBuf>, Vec<PathBuf>) = files.into_iter().partition(|path| {
+        path.extension().is_some_and(|ext| ext == sidecar::SIDECAR_EXTENSION) && scanned.contains(&path.with_extension(""))
+    });
+    let sidecars: HashSet<PathBuf> = sidecars.into_iter().collect();
+
+    let collated_prefixes = match &args.collate_prefix {
+        Some(delimiter) => shared_prefixes(&files, delimiter),
+        None => HashSet::new(),
+    };
+
+    let mut volume_counts = HashMap::new();
+    if args.volume_threshold.is_some() {
+        for file in &files {
+            *volume_counts.entry(args.volume_key.of(file)).or_default() += 1;
+        }
+    }
+
+    let authors = if args.mode == Mode::GitAuthor {
+        git::last_authors(&root).map_err(OrganizeError::GitHistory)?
+    } else {
+        HashMap::new()
+    };
+
+    let duplicate_groups = if args.cluster_duplicates {
+        duplicates::groups(&files, args.fingerprint_window, &cancelled).await
+    } else {
+        HashMap::new()
+    };
+
+    let archive = match &args.skip_if_in {
+        Some(archive) => {
+            let mut sizes = HashSet::new();
+            for file in &files {
+                if let Ok(metadata) = fs::metadata(file).await {
+                    sizes.insert(metadata.len());
+                }
+            }
+            let index = duplicates::archive_index(archive, &sizes, &cancelled)
+                .await
+                .map_err(|err| OrganizeError::ReadDir(archive.clone(), err))?;
+            Some(index)
+        }
+        None => None,
+    };
+    if cancelled.load(Ordering::Relaxed) {
+        return Err(args.interrupted(Summary::default()));
+    }
+
+    let aliases = Aliases::new(&args.ext_alias);
+    let job_limit = args.job_limit();
+    let taken_names = if args.rename_collision_detect_global {
+        naming::existing_names(&destination_root)
+    } else {
+        naming::TakenNames::new()
+    };
+    let trash_folder = trash::run_folder(&destination_root, Local::now());
+    let log = match &args.log_file {
+        Some(path) => Some(
+            LogFile::open(path, args.log_max_bytes)
+                .await
+                .map_err(|err| OrganizeError::OpenLog(path.clone(), err))?,
+        ),
+        None => None,
+    };
+
+    let context = Arc::new(Context {
+        args,
+        root,
+        destination_root,
+        collated_prefixes,
+        volume_counts,
+        filters,
+        authors,
+        aliases,
+        atime_warned: AtomicBool::new(false),
+        time_source_warned: AtomicBool::new(false),
+        trash_folder,
+        taxonomy,
+        cancelled,
+        log,
+        taken_names: std::sync::Mutex::new(taken_names),
+        sources,
+        progress: Progress::new(),
+        #[cfg(unix)]
+        load_gate: Mutex::new(()),
+        duplicate_groups,
+        archive,
+        claimed_destinations: std::sync::Mutex::new(HashMap::new()),
+        destination_locks: std::sync::Mutex::new(HashMap::new()),
+        permits: Semaphore::new(job_limit),
+        allowed_dests,
+        mirror,
+        rejected: std::sync::Mutex::new(Vec::new()),
+    });
+
+    if let Some(file_path) = &context.args.explain {
+        explain_file(file_path.clone(), Arc::clone(&context)).await;
+        if context.cancelled.load(Ordering::Relaxed) {
+            return Err(context.args.interrupted(Summary::default()));
+        }
+        return Ok(());
+    }
+
+    if context.args.prescan || context.args.prescan_strict {
+        let unreadable = prescan(&files).await;
+        if !unreadable.is_empty() {
+            eprintln!("Cannot read {} file(s):", unreadable.len());
+            for (path, err) in &unreadable {
+                eprintln!("  {}: {}", path.display(), err);
+            }
+            if context.args.prescan_strict {
+                return Err(OrganizeError::Unreadable(unreadable.len()));
+            }
+        }
+        if context.cancelled.load(Ordering::Relaxed) {
+            return Err(context.args.interrupted(Summary::default()));
+        }
+    }
+
+    if context.args.count_only {
+        let count = count_eligible(files, Arc::clone(&context)).await;
+        // A count cut short would undercount, so none is printed
+        if context.cancelled.load(Ordering::Relaxed) {
+            return Err(context.args.interrupted(Summary::default()));
+        }
+        println!("{}", count);
+        return Ok(());
+    }
+
+    context.log(|| format!("organizing {}", context.root.display())).await;
+    for (link, reason) in &skipped_links {
+        context.args.output().verbose(2, || format!("skipped {}: {}", link.display(), reason));
+        context.log(|| format!("skipped {}: {}", link.display(), reason)).await;
+    }
+
+    if let Some(days) = context.args.trash_retention {
+        if !context.args.dry_run && context.args.emit_script.is_none() {
+            let cutoff = Local::now().naive_local() - chrono::Duration::days(days.into());
+            let trash = context.destination_root.join(trash::TRASH_DIR);
+            let expired = trash::expire(&context.destination_root, cutoff, &context.cancelled)
+                .await
+                .map_err(|err| OrganizeError::Remove(trash, err))?;
+            context.log(|| format!("expired {} trashed files", expired)).await;
+            if context.args.summary_format() == SummaryFormat::Human {
+                println!("Expired {} trashed file(s) older than {} days.", expired, days);
+            }
+            if context.cancelled.load(Ordering::Relaxed) {
+                return Err(context.args.interrupted(Summary::default()));
+            }
+        }
+    }
+
+    if let Some(fraction) = context.args.sample {
+        files.retain(|path| in_sample(path, &context.root, fraction));
+    }
+
+    let mut moves = Vec::new();
+    let mut failures = Vec::new();
+    if let Some(manifest_path) = &context.args.undo {
+        let entries = manifest::read(manifest_path)
+            .await
+            .map_err(|err| OrganizeError::Read(manifest_path.clone(), err))?;
+        for entry in entries.into_iter().rev() {
+            // A file that's no longer where the manifest left it can't be moved back
+            let bytes = match fs::metadata(&entry.to).await {
+                Ok(metadata) => metadata.len(),
+                Err(err) => {
+                    failures.push(OrganizeError::Metadata(entry.to, err));
+                    continue;
+                }
+            };
+            moves.push(PlannedMove {
+                from: entry.to,
+                to: entry.from,
+                bytes,
+                datetime: None,
+                folder_dates: Vec::new(),
             });
-            tasks.push(task);
         }
     }
 
+    let mut tasks = Vec::new();
+
+    for path in files {
+        let context = Arc::clone(&context);
+        let task = tokio::task::spawn_local(async move {
+            let _permit = context.permits.acquire().await.unwrap();
+            if context.cancelled.load(Ordering::Relaxed) {
+                return None;
+            }
+            plan_file(path, Arc::clone(&context), &mut Trace::off()).await
+        });
+        tasks.push(task);
+    }
+
+    let mut skipped = skipped_links.len() as u64;
     for task in tasks {
-        task.await.expect("Task failed");
+        match task.await {
+            Ok(Some(planned)) => {
+                let sidecar = sidecar::path(&planned.from);
+                let sidecar_move = sidecars.contains(&sidecar).then(|| PlannedMove {
+                    bytes: std::fs::metadata(&sidecar).map_or(0, |metadata| metadata.len()),
+                    to: sidecar::path(&planned.to),
+                    from: sidecar,
+                    datetime: planned.datetime,
+                    folder_dates: Vec::new(),
+                });
+                moves.push(planned);
+                moves.extend(sidecar_move);
+            }
+            Ok(None) => skipped += 1,
+            Err(err) => failures.push(OrganizeError::Task(err)),
+        }
     }
-}
+    // Nothing has moved yet, and a plan missing the files Ctrl-C cut off mustn't be carried out or written
+    if context.cancelled.load(Ordering::Relaxed) {
+        return Err(context.args.interrupted(Summary::default()));
+    }
+
+    let rejected = std::mem::take(&mut *context.rejected.lock().unwrap());
+    if !rejected.is_empty() {
+        eprintln!("{} file(s) have no allowed destination and were left in place:", rejected.len());
+        for (file, folder) in &rejected {
+            eprintln!("  {} -> {}", file.display(), folder.display());
+        }
+    }
+
+    if let Some(max_operations) = context.args.max_operations {
+        if moves.len() > max_operations && !context.args.force {
+            return Err(OrganizeError::LimitExceeded { planned: moves.len(), limit: max_operations });
+        }
+    }
+
+    if let Some(plan_path) = &context.args.plan_canonical {
+        let link_moves: Vec<_> = symlinks
+            .iter()
+            .map(|link| (link, context.destination_root.join(symlink::SYMLINKS_DIR).join(link.file_name().unwrap())))
+            .collect();
+        let pairs = moves
+            .iter()
+            .map(|planned| (planned.from.as_path(), planned.to.as_path()))
+            .chain(link_moves.iter().map(|(link, destination)| (link.as_path(), destination.as_path())));
+        let plan = plan_canonical::render(&context.root, &context.destination_root, pairs);
+        fs::write(plan_path, plan).await.map_err(|err| OrganizeError::Write(plan_path.clone(), err))?;
+    }
+
+    // Like the other plan outputs, the script is written by a dry run too
+    if let Some(script_path) = &context.args.emit_script {
+        let pairs = moves.iter().map(|planned| (planned.from.as_path(), planned.to.as_path()));
+        let overwrite = context.args.on_conflict == ConflictPolicy::Overwrite;
+        script::write(script_path, pairs, context.args.copy, overwrite)
+            .await
+            .map_err(|err| OrganizeError::Write(script_path.clone(), err))?;
+        context.args.output().info(|| format!("Wrote {} moves to {}", moves.len(), script_path.display()));
+        if !context.args.dry_run {
+            return Ok(());
+        }
+    }
+
+    if context.args.dry_run {
+        let output = context.args.output();
+        for planned in &moves {
+            let verb = if context.args.copy { "would copy" } else { "would move" };
+            output.info(|| describe_move(verb, &planned.from, &planned.to));
+        }
+        for link in &symlinks {
+            let destination = context.destination_root.join(symlink::SYMLINKS_DIR).join(link.file_name().unwrap());
+            output.info(|| describe_move("would move link", link, &destination));
+        }
+        if let Some(dot_path) = &context.args.plan_dot {
+            let dot = plan_dot::render(&context.destination_root, moves.iter().map(|planned| planned.to.as_path()));
+            fs::write(dot_path, dot).await.map_err(|err| OrganizeError::Write(dot_path.clone(), err))?;
+        }
+        return Ok(());
+    }
+
+    if let Some(staging) = &context.args.staging {
+        fs::create_dir_all(staging).await.map_err(|err| OrganizeError::CreateDir(staging.clone(), err))?;
+    }
+
+    #[cfg(unix)]
+    let progress_listener = {
+        let context = Arc::clone(&context);
+        tokio::task::spawn_local(async move {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let Ok(mut signals) = signal(SignalKind::user_defined1()) else {
+                return;
+            };
+            while signals.recv().await.is_some() {
+                let report = context.progress.report();
+                // Log lines land in the file as they're written, so `tail -f` shows the report right away
+                if context.log.is_some() {
+                    context.log(|| report).await;
+                } else {
+                    eprintln!("{}", report);
+                }
+            }
+        })
+    };
+
+    let folder_dates: BTreeMap<PathBuf, NaiveDate> = if context.args.touch_destination_dirs {
+        moves.iter().flat_map(|planned| planned.folder_dates.iter().cloned()).collect()
+    } else {
+        BTreeMap::new()
+    };
 
-async fn reverse_organize_dir(current_dir: PathBuf, target_dir: Arc<Mutex<String>>) {
-    let mut entries = fs::read_dir(&current_dir).await.expect("Failed to read directory");
+    let staged_path = |index: usize, planned: &PlannedMove| {
+        context.args.staging.as_ref().map(|staging| {
+            let file_name = planned.from.file_name().unwrap().to_string_lossy();
+            staging.join(format!("{}-{}.partial", index, file_name))
+        })
+    };
 
+    let bar = context.args.progress_bar(moves.len() as u64);
     let mut tasks = Vec::new();
+    for (index, planned) in moves.into_iter().enumerate() {
+        let staged_path = staged_path(index, &planned);
+        let retry = context.args.retry_failed_pass.then(|| (index, planned.clone()));
+        let context = Arc::clone(&context);
+        let bar = bar.clone();
+        let task = tokio::task::spawn_local(async move {
+            let _permit = context.permits.acquire().await.unwrap();
+            #[cfg(unix)]
+            context.wait_for_low_load().await;
+            context.progress.start();
+            let record = execute_move(planned, staged_path, &context).await;
+            match &record {
+                Ok(moved) => {
+                    context.progress.finish(moved.is_some());
+                    if let Some(moved) = moved {
+                        context.args.output().verbose(1, || moved.describe(context.args.copy));
+                    }
+                }
+                Err(err) => {
+                    context.progress.finish(false);
+                    context.progress.error();
+                    context.log(|| format!("error: {}", err)).await;
+                }
+            }
+            bar.inc(1);
+            (record, retry)
+        });
+        tasks.push(task);
+    }
 
-    while let Some(entry) = entries.next_entry().await.expect("Failed to read entry") {
-        let path = entry.path();
-        if path.is_file() {
-            let target_dir_clone = Arc::clone(&target_dir);
-            let task = tokio::task::spawn_local(async move {
-                let new_file_path = {
-                    let target_dir = target_dir_clone.lock().await;
-                    PathBuf::from(&*target_dir).join(path.file_name().unwrap())
-                };
-                fs::rename(&path, &new_file_path).await.expect("Failed to move file");
-            });
-            tasks.push(task);
-        } else if path.is_dir() {
-            let target_dir_clone = Arc::clone(&target_dir);
-            let task = tokio::task::spawn_local(async move {
-                reverse_organize_dir(path, target_dir_clone).await;
+    let mut summary = Summary {
+        files_skipped: skipped,
+        ..Summary::default()
+    };
+    let mut records = Vec::new();
+    let mut retries = Vec::new();
+    for task in tasks {
+        match task.await {
+            Ok((Ok(Some(record)), _)) => {
+                summary.record_move(record.bytes, record.year());
+                records.push(record);
+            }
+            Ok((Ok(None), _)) => summary.files_skipped += 1,
+            Ok((Err(err), Some(retry))) => retries.push((retry, err)),
+            Ok((Err(err), None)) => failures.push(err),
+            Err(err) => failures.push(OrganizeError::Task(err)),
+        }
+    }
+    bar.finish_and_clear();
+
+    // Once --max-errors has stopped the run, a retry pass would only skip every file, so the
+    // original failures stand
+    if context.too_many_errors() {
+        failures.extend(retries.drain(..).map(|(_, err)| err));
+    }
+    if !retries.is_empty() {
+        let attempted = retries.len();
+        let mut recovered = 0;
+        for ((index, planned), _) in retries {
+            let staged_path = staged_path(index, &planned);
+            match execute_move(planned, staged_path, &context).await {
+                Ok(Some(record)) => {
+                    context.args.output().verbose(1, || record.describe(context.args.copy));
+                    context.log(|| format!("retry succeeded: {}", record.from.display())).await;
+                    summary.record_move(record.bytes, record.year());
+                    records.push(record);
+                    recovered += 1;
+                }
+                Ok(None) => summary.files_skipped += 1,
+                Err(err) => failures.push(err),
+            }
+        }
+        if context.args.summary_format() == SummaryFormat::Human {
+            println!("Retry pass: {} of {} failed files moved.", recovered, attempted);
+        }
+    }
+
+    for link in symlinks {
+        if context.cancelled.load(Ordering::Relaxed) || context.too_many_errors() {
+            break;
+        }
+        let destination = context.destination_root.join(symlink::SYMLINKS_DIR).join(link.file_name().unwrap());
+        let destination = claim_free_destination(&link, &destination, &context.claimed_destinations);
+        match symlink::relocate(&link, &destination).await {
+            Ok(()) => {
+                context.args.output().verbose(1, || describe_move("moved link", &link, &destination));
+                context.log(|| format!("moved link {} -> {}", link.display(), destination.display())).await;
+                summary.record_move(0, None);
+            }
+            Err(source) => {
+                context.progress.error();
+                failures.push(OrganizeError::Move { from: link, to: destination, source });
+            }
+        }
+    }
+    interrupt_listener.abort();
+    #[cfg(unix)]
+    progress_listener.abort();
+
+    if context.args.folder_readme {
+        let mut folders: BTreeMap<&Path, (usize, Vec<NaiveDateTime>)> = BTreeMap::new();
+        for record in &records {
+            let (count, dates) = folders.entry(record.to.parent().unwrap()).or_default();
+            *count += 1;
+            dates.extend(record.datetime);
+        }
+        for (folder, (count, dates)) in &folders {
+            let name = &context.args.folder_readme_name;
+            match folder_readme::write(folder, name, *count, dates, context.args.folder_readme_overwrite).await {
+                Ok(true) => {}
+                Ok(false) => context.log(|| format!("kept existing {}", folder.join(name).display())).await,
+                Err(err) => eprintln!("Failed to write {}: {}", folder.join(name).display(), err),
+            }
+        }
+    }
+
+    for (folder, date) in &folder_dates {
+        let timestamp = date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
+        if let Err(err) = filetime::set_file_mtime(folder, FileTime::from_unix_time(timestamp, 0)) {
+            eprintln!("Failed to set modified time of {}: {}", folder.display(), err);
+        }
+    }
+
+    if let Some(manifest_path) = &context.args.manifest {
+        let pairs = records.iter().map(|record| (record.from.as_path(), record.to.as_path()));
+        manifest::write(manifest_path, pairs)
+            .await
+            .map_err(|err| OrganizeError::Write(manifest_path.clone(), err))?;
+    }
+
+    summary.files_failed = failures.len() as u64;
+    summary.completed = !context.cancelled.load(Ordering::Relaxed) && !context.too_many_errors();
+    context
+        .log(|| {
+            if summary.completed {
+                format!("finished: {} files moved", summary.files_moved)
+            } else {
+                format!("interrupted: {} files moved", summary.files_moved)
+            }
+        })
+        .await;
+    // An undo isn't an organizing run, so it neither compares against nor replaces the last one's stats
+    let undoing = context.args.undo.is_some();
+    let previous = if undoing { None } else { summary::load_previous(&context.destination_root).await };
+    // Stats live with the organized tree; an `--output` that received nothing doesn't exist to hold them
+    if summary.completed && !undoing && context.destination_root.is_dir() {
+        summary::save(&context.destination_root, &summary).await;
+    }
+    summary.print(previous.as_ref(), context.args.summary_format());
+
+    if context.args.verify_after_run {
+        verify_moves(&records, context.args.copy, context.args.summary_format()).await?;
+    }
+
+    if context.too_many_errors() {
+        eprintln!("Stopped early: {} errors reached --max-errors.", context.progress.errors());
+    }
+    if !failures.is_empty() {
+        return Err(OrganizeError::Failed(failures));
+    }
+
+    if !summary.completed {
+        return Err(OrganizeError::Interrupted);
+    }
+    Ok(())
+}
+
+/// Works out where a file belongs, without moving it.
+async fn plan_file(file_path: PathBuf, context: Arc<Context>, trace: &mut Trace) -> Option<PlannedMove> {
+    let Ok(metadata) = fs::metadata(&file_path).await else {
+        trace.step(|| "metadata: unreadable, file is left in place".to_string());
+        return None;
+    };
+
+    let route = match context.filters.should_process(&file_path) {
+        Decision::Process(route) => route,
+        Decision::Skip(reason) => {
+            trace.step(|| format!("filtered: {}, file is left in place", reason));
+            context.args.output().verbose(2, || format!("skipped {}: {}", file_path.display(), reason));
+            context.log(|| format!("skipped {}: {}", file_path.display(), reason)).await;
+            return None;
+        }
+    };
+    if let Err(reason) = filter_file(&file_path, &metadata, &context).await {
+        trace.step(|| format!("filtered: {}, file is left in place", reason));
+        context.args.output().verbose(2, || format!("skipped {}: {}", file_path.display(), reason));
+        context.log(|| format!("skipped {}: {}", file_path.display(), reason)).await;
+        return None;
+    }
+
+    if let Some(archive) = &context.archive {
+        if duplicates::is_archived(&file_path, metadata.len(), archive).await {
+            if !context.args.trash_archived {
+                trace.step(|| "archived: an identical file is in the archive, file is left in place".to_string());
+                context.log(|| format!("skipped {}: already archived", file_path.display())).await;
+                return None;
+            }
+            trace.step(|| "archived: an identical file is in the archive, moving it to the trash".to_string());
+            let relative = file_path.strip_prefix(&context.root).unwrap_or(&file_path);
+            let to = context.trash_folder.join(relative);
+            return Some(PlannedMove {
+                from: file_path,
+                to,
+                bytes: metadata.len(),
+                datetime: None,
+                folder_dates: Vec::new(),
             });
-            tasks.push(task);
         }
     }
 
-    for task in tasks {
-        task.await.expect("Task failed");
+    let mut base_folder = match context.sources.get(&file_path) {
+        Some(name) => {
+            trace.step(|| format!("root: scanned from `{}`", name));
+            context.destination_root.join(name)
+        }
+        None => context.destination_root.clone(),
+    };
+    if let Some(threshold) = context.args.volume_threshold {
+        let key = context.args.volume_key.of(&file_path);
+        let count = context.volume_counts.get(&key).copied().unwrap_or(0);
+        let bucket = if count > threshold { "high-volume" } else { "low-volume" };
+        trace.step(|| format!("volume: {} files share `{}`, {}", count, key, bucket));
+        base_folder.push(bucket);
+    }
+    if context.args.by_type {
+        let ext = file_path.extension().map(|ext| ext.to_string_lossy()).unwrap_or_default();
+        let category = category::categorize(&ext);
+        trace.step(|| format!("type: {}", category.folder_name()));
+        base_folder.push(category.folder_name());
+    }
+
+    let resolved = resolve_datetime(&file_path, &metadata, &context);
+    if let Some((datetime, source)) = &resolved {
+        context.args.output().verbose(1, || format!("dated {} by {}: {}", file_path.display(), source.name(), datetime));
+    }
+    let datetime = match resolved {
+        Some((datetime, DateSource::Filename)) => {
+            trace.step(|| format!("timestamp: from file name {}", datetime));
+            Some(datetime)
+        }
+        Some((datetime, DateSource::Exif)) => {
+            trace.step(|| format!("timestamp: EXIF capture time {}", datetime));
+            Some(datetime)
+        }
+        Some((datetime, source)) => {
+            trace.step(|| format!("timestamp: {} {} (UTC)", source.name(), datetime));
+            Some(datetime)
+        }
+        None => {
+            trace.step(|| "timestamp: unavailable".to_string());
+            None
+        }
+    };
+    let datetime = match datetime {
+        None if context.args.on_missing_timestamp == MissingTimestamp::Now => {
+            let now = chrono::Utc::now().naive_utc();
+            trace.step(|| format!("timestamp: using now {} (UTC)", now));
+            Some(now)
+        }
+        datetime => datetime,
+    };
+    let (rule, mode) = match route {
+        Route::Rule(rule) => (Some(rule), context.args.mode),
+        Route::Mode(mode) => (None, mode),
+    };
+    let component = context
+        .args
+        .group_by_component
+        .and_then(|index| source_component(&file_path, &context.root, index));
+    let collated = context.args.collate_prefix.as_deref().and_then(|delimiter| {
+        let prefix = file_prefix(&file_path, delimiter)?;
+        context.collated_prefixes.contains(prefix).then(|| prefix.to_string())
+    });
+
+    let mut folder_dates = Vec::new();
+    let duplicate_group = context.duplicate_groups.get(&file_path);
+    let target_folder = if let Some(group) = duplicate_group {
+        trace.step(|| format!("duplicates: identical copies share {}", group));
+        base_folder.join(duplicates::DUPLICATES_DIR).join(group)
+    } else if let Some(rule) = rule {
+        trace.step(|| format!("rule: matches `{}`", rule.pattern()));
+        match &datetime {
+            Some(datetime) => base_folder.join(rule.destination(datetime)),
+            None => undated_folder(&file_path, &base_folder, &context, trace).await?,
+        }
+    } else if let Some(component) = component {
+        trace.step(|| format!("source component: {}", component.to_string_lossy()));
+        base_folder.join(component)
+    } else if let Some(prefix) = collated {
+        trace.step(|| format!("collated prefix: {}", prefix));
+        base_folder.join(prefix)
+    } else if let Some(mirror) = &context.mirror {
+        match mirror.get(file_path.file_name().unwrap()) {
+            Some(folder) => {
+                trace.step(|| match folder.as_os_str().is_empty() {
+                    true => "mirror: reference file is at the top of the tree".to_string(),
+                    false => format!("mirror: reference file is in `{}`", folder.display()),
+                });
+                base_folder.join(folder)
+            }
+            None => {
+                trace.step(|| "mirror: no reference file of that name".to_string());
+                base_folder.join(mirror::UNMATCHED_DIR)
+            }
+        }
+    } else if let Some(folder) = mode_folder(mode, &file_path, &metadata, datetime.as_ref(), &context).await {
+        trace.step(|| format!("mode {}: {}", mode.name(), folder.display()));
+        base_folder.join(folder)
+    } else if let Some(datetime) = &datetime {
+        let (folder, dates) = date_folder(datetime, &base_folder, &context.args.date_layout(), trace);
+        folder_dates = dates;
+        folder
+    } else {
+        undated_folder(&file_path, &base_folder, &context, trace).await?
+    };
+
+    if let Some(allowed_dests) = &context.allowed_dests {
+        if !allowed_dests.allows(&target_folder, &context.destination_root) {
+            trace.step(|| format!("{} is not an allowed destination, file is left in place", target_folder.display()));
+            context.log(|| format!("rejected {}: {} is not allowed", file_path.display(), target_folder.display())).await;
+            context.rejected.lock().unwrap().push((file_path, target_folder));
+            return None;
+        }
+    }
+
+    let file_name = if context.args.normalize_ext_names {
+        context.aliases.normalized_file_name(&file_path).map(OsString::from)
+    } else {
+        None
+    };
+    let mut file_name = file_name.unwrap_or_else(|| file_path.file_name().unwrap().to_os_string());
+    // A file already in place keeps its name, so it's checked before the name is made unique
+    if target_folder.join(&file_name) == file_path {
+        trace.step(|| format!("destination: {}", file_path.display()));
+        trace.step(|| "already in place".to_string());
+        return None;
+    }
+    // Copying again into a tree an earlier `--copy` built finds each file already there
+    let existing = target_folder.join(&file_name);
+    if context.args.copy && duplicates::is_identical(&file_path, metadata.len(), &existing).await {
+        trace.step(|| format!("destination: {}", existing.display()));
+        trace.step(|| "an identical copy is already there".to_string());
+        context.log(|| format!("skipped {}: already copied to {}", file_path.display(), existing.display())).await;
+        return None;
+    }
+    // Claiming is the last step that can change the plan, so a file left in place for any reason
+    // never holds on to a name a moving file could have used
+    if context.args.rename_collision_detect_global {
+        let unique = naming::claim_unique(&mut context.taken_names.lock().unwrap(), file_name.clone(), &file_path);
+        if unique != file_name {
+            trace.step(|| format!("name: {} is already used in the tree, renamed", file_name.to_string_lossy()));
+        }
+        file_name = unique;
+    }
+    let new_file_path = target_folder.join(file_name);
+    trace.step(|| format!("destination: {}", new_file_path.display()));
+    // Settled while planning, so a dry run, a script, the plan and the run itself all agree on names
+    let new_file_path = match context.args.on_conflict {
+        ConflictPolicy::Rename => {
+            let free = claim_free_destination(&file_path, &new_file_path, &context.claimed_destinations);
+            if free != new_file_path {
+                trace.step(|| format!("conflict: {} is taken, renamed to {}", new_file_path.display(), free.display()));
+            }
+            free
+        }
+        ConflictPolicy::Skip => {
+            if !claim_destination(&file_path, &new_file_path, &context.claimed_destinations) {
+                trace.step(|| format!("conflict: {} is taken, file is left in place", new_file_path.display()));
+                context.log(|| format!("conflict at {}: skipped {}", new_file_path.display(), file_path.display())).await;
+                return None;
+            }
+            new_file_path
+        }
+        ConflictPolicy::Overwrite | ConflictPolicy::KeepNewest | ConflictPolicy::KeepLargest => new_file_path,
+    };
+    Some(PlannedMove {
+        from: file_path,
+        to: new_file_path,
+        bytes: metadata.len(),
+        datetime,
+        folder_dates,
+    })
+}
+
+/// Applies `--on-missing-timestamp` to a file that needs a date but has none.
+async fn undated_folder(file_path: &Path, base_folder: &Path, context: &Context, trace: &mut Trace) -> Option<PathBuf> {
+    match context.args.on_missing_timestamp {
+        MissingTimestamp::Undated => {
+            trace.step(|| "no timestamp: moving to `undated`".to_string());
+            Some(base_folder.join("undated"))
+        }
+        MissingTimestamp::Skip | MissingTimestamp::Now => {
+            trace.step(|| "no timestamp: file is left in place".to_string());
+            context.log(|| format!("skipped {}: no timestamp", file_path.display())).await;
+            None
+        }
+    }
+}
+
+/// Whether the file's extension is one of `extensions`, ignoring case and a leading dot.
+fn has_listed_extension(file_path: &Path, extensions: &[String]) -> bool {
+    let Some(ext) = file_path.extension() else {
+        return false;
+    };
+    let ext = ext.to_string_lossy();
+    extensions.iter().any(|listed| listed.trim_start_matches('.').eq_ignore_ascii_case(&ext))
+}
+
+/// Checks a file against the size, date and content filters, returning why it was filtered out.
+async fn filter_file(file_path: &Path, metadata: &Metadata, context: &Context) -> Result<(), String> {
+    let args = &context.args;
+    let size = metadata.len();
+    if args.min_size.is_some_and(|min_size| size < min_size) {
+        return Err(format!("{} bytes, under --min-size", size));
+    }
+    if args.max_size.is_some_and(|max_size| size > max_size) {
+        return Err(format!("{} bytes, over --max-size", size));
+    }
+    if args.since.is_some() || args.until.is_some() {
+        // The same timestamp the destination folder is picked from, so undated files are never filtered
+        let datetime = match resolve_datetime(file_path, metadata, context) {
+            Some((datetime, _)) => Some(datetime),
+            None if args.on_missing_timestamp == MissingTimestamp::Now => Some(chrono::Utc::now().naive_utc()),
+            None => None,
+        };
+        if let Some(date) = datetime.map(|datetime| datetime.date()) {
+            let too_early = args.since.is_some_and(|since| date < since);
+            let too_late = args.until.is_some_and(|until| date > until);
+            if too_early || too_late {
+                return Err(format!("dated {}, outside --since/--until", date));
+            }
+        }
+    }
+    if let Some(pattern) = &args.content_match {
+        let prefix = content::read_prefix(file_path, args.content_scan_bytes)
+            .await
+            .map_err(|err| format!("content unreadable ({})", err))?;
+        if content::is_binary(&prefix) {
+            return Err("binary content".to_string());
+        }
+        if !pattern.is_match(&String::from_utf8_lossy(&prefix)) {
+            return Err(format!("content does not match `{}`", pattern));
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `path` is a `--folder-readme` file named `readme_name` left by an earlier run.
+fn is_folder_readme(path: &Path, readme_name: &str) -> bool {
+    path.file_name().is_some_and(|name| name == readme_name) && folder_readme::is_folder_readme(path)
+}
+
+/// Sets `cancelled` when Ctrl-C is pressed, instead of letting it kill the process.
+fn listen_for_interrupt(cancelled: Arc<AtomicBool>) -> tokio::task::JoinHandle<()> {
+    tokio::task::spawn_local(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+    })
+}
+
+/// Tries to open every file for reading, returning the ones that can't be accessed.
+async fn prescan(files: &[PathBuf]) -> Vec<(PathBuf, std::io::Error)> {
+    let mut tasks = Vec::new();
+    for path in files {
+        let path = path.clone();
+        let task = tokio::task::spawn_local(async move {
+            match fs::File::open(&path).await {
+                Ok(_) => None,
+                Err(err) => Some((path, err)),
+            }
+        });
+        tasks.push(task);
+    }
+
+    let mut unreadable = Vec::new();
+    for (path, task) in files.iter().zip(tasks) {
+        match task.await {
+            Ok(Some(failure)) => unreadable.push(failure),
+            Ok(None) => {}
+            Err(err) => unreadable.push((path.clone(), std::io::Error::other(err))),
+        }
+    }
+    unreadable
+}
+
+/// Counts the files that pass the selection filters.
+async fn count_eligible(files: Vec<PathBuf>, context: Arc<Context>) -> usize {
+    let mut tasks = Vec::new();
+    for path in files {
+        let context = Arc::clone(&context);
+        let task = tokio::task::spawn_local(async move {
+            if context.cancelled.load(Ordering::Relaxed) {
+                return false;
+            }
+            let Ok(metadata) = fs::metadata(&path).await else {
+                return false;
+            };
+            matches!(context.filters.should_process(&path), Decision::Process(_))
+                && filter_file(&path, &metadata, &context).await.is_ok()
+        });
+        tasks.push(task);
+    }
+
+    let mut count = 0;
+    for task in tasks {
+        match task.await {
+            Ok(true) => count += 1,
+            Ok(false) => {}
+            Err(err) => eprintln!("{}", OrganizeError::Task(err)),
+        }
+    }
+    count
+}
+
+/// Prints each step of the destination computation for a single file.
+async fn explain_file(file_path: PathBuf, context: Arc<Context>) {
+    println!("Explaining {}", file_path.display());
+
+    let mut trace = Trace::on();
+    let planned = plan_file(file_path, context, &mut trace).await;
+    for step in trace.0.unwrap_or_default() {
+        println!("  {}", step);
+    }
+    if planned.is_none() {
+        println!("  result: not moved");
+    }
+}
+
+/// Returns the folder the selected mode puts a file in, or `None` to fall back to dates.
+async fn mode_folder(
+    mode: Mode,
+    file_path: &Path,
+    metadata: &Metadata,
+    datetime: Option<&NaiveDateTime>,
+    context: &Context,
+) -> Option<PathBuf> {
+    match mode {
+        Mode::Date | Mode::FilenameDate => None,
+        Mode::GitAuthor => {
+            let canonical = fs::canonicalize(file_path).await.ok()?;
+            let author = context.authors.get(&canonical)?;
+            Some(PathBuf::from(git::author_folder(author)))
+        }
+        Mode::Extension => context.aliases.normalized_extension(file_path).map(PathBuf::from),
+        Mode::Recency => Some(PathBuf::from(recency_bucket(metadata, context)?)),
+        Mode::Taxonomy => Some(PathBuf::from(closest_folder(file_path, context))),
+        Mode::CategoryYear => {
+            let ext = file_path.extension().map(|ext| ext.to_string_lossy()).unwrap_or_default();
+            let category = category::categorize(&ext);
+            Some(PathBuf::from(format!("{}-{}", category.folder_name(), datetime?.year())))
+        }
+        Mode::Language => {
+            let prefix = content::read_prefix(file_path, content::LANGUAGE_SCAN_BYTES).await.ok()?;
+            content::detect_language(&prefix).map(PathBuf::from)
+        }
+        Mode::Encoding => {
+            let prefix = content::read_prefix(file_path, content::ENCODING_SCAN_BYTES).await.ok()?;
+            let truncated = prefix.len() as u64 == content::ENCODING_SCAN_BYTES;
+            content::detect_encoding(&prefix, truncated).map(PathBuf::from)
+        }
+        Mode::Number => {
+            let number = file_number(file_path, context.args.number_regex.as_ref()?)?;
+            Some(PathBuf::from(format!("{:04}", number / context.args.bucket_size)))
+        }
+        Mode::Shard => Some(PathBuf::from(format!("shard-{:02x}", path_shard(file_path, &context.root)))),
+        Mode::Color => {
+            let ext = file_path.extension()?.to_string_lossy();
+            if category::categorize(&ext) != category::Category::Images {
+                return None;
+            }
+            let path = file_path.to_path_buf();
+            let color = tokio::task::spawn_blocking(move || color::dominant_color(&path)).await.ok()??;
+            Some(PathBuf::from(color))
+        }
+    }
+}
+
+/// Extracts the number captured by the first group of `pattern` from the file name.
+fn file_number(file_path: &Path, pattern: &Regex) -> Option<u64> {
+    let file_name = file_path.file_name()?.to_str()?;
+    let captures = pattern.captures(file_name)?;
+    let digits = captures.get(1).or_else(|| captures.get(0))?;
+    digits.as_str().parse().ok()
+}
+
+/// Hashes the file's path relative to `root`.
+///
+/// Uses FNV-1a so a path hashes the same on every run and platform.
+fn path_hash(file_path: &Path, root: &Path) -> u64 {
+    let relative = file_path.strip_prefix(root).unwrap_or(file_path);
+    relative
+        .as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Picks one of 256 shards for the file from its path hash.
+fn path_shard(file_path: &Path, root: &Path) -> u8 {
+    let hash = path_hash(file_path, root);
+    (hash ^ (hash >> 32) ^ (hash >> 16) ^ (hash >> 8)) as u8
+}
+
+/// Whether the file falls in a `--sample` of the given fraction, decided by its path hash.
+fn in_sample(file_path: &Path, root: &Path, fraction: f64) -> bool {
+    (path_hash(file_path, root) as f64 / u64::MAX as f64) < fraction
+}
+
+/// Parses a `--min-size` or `--max-size` such as `4096`, `500k`, `10MB` or `2GiB`.
+///
+/// `k`, `M`, `G` and `T` (with or without a trailing `B`) are powers of 1000, and `KiB`, `MiB`,
+/// `GiB` and `TiB` powers of 1024. Units are case-insensitive.
+fn parse_size(value: &str) -> Result<u64, String> {
+    let invalid = || format!("expected a size such as `4096`, `500k` or `10MB`, found `{}`", value);
+    let split = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "m" | "mb" => 1_000_000,
+        "g" | "gb" => 1_000_000_000,
+        "t" | "tb" => 1_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+/// Parses a `--since` or `--until` day.
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| format!("expected a YYYY-MM-DD date, found `{}`", value))
+}
+
+/// Parses a `--sample` fraction between 0 (exclusive) and 1.
+fn parse_fraction(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(fraction) if fraction > 0.0 && fraction <= 1.0 => Ok(fraction),
+        _ => Err(format!("expected a fraction between 0 and 1, found `{}`", value)),
+    }
+}
+
+/// Parses a `--year-format`, `--month-format` or `--week-format` strftime string.
+///
+/// Folders are named by date, so time fields such as `%H` are rejected along with unknown
+/// specifiers and formats that would produce an empty name or a path separator.
+fn parse_folder_format(value: &str) -> Result<String, String> {
+    use chrono::format::{Item, StrftimeItems};
+    use std::fmt::Write;
+
+    if StrftimeItems::new(value).any(|item| item == Item::Error) {
+        return Err(format!("`{}` is not a valid strftime format", value));
+    }
+    let mut sample = String::new();
+    if write!(sample, "{}", NaiveDate::from_ymd_opt(2024, 3, 11).unwrap().format(value)).is_err() {
+        return Err(format!("`{}` uses a time field, but folders are named by date only", value));
+    }
+    if sample.trim().is_empty() || sample.contains(['/', '\\']) || sample == "." || sample == ".." {
+        return Err(format!("`{}` does not produce a usable folder name (got `{}`)", value, sample));
+    }
+    Ok(value.to_string())
+}
+
+/// Picks the existing folder whose name is most similar to the file name, or `unsorted`.
+fn closest_folder<'a>(file_path: &Path, context: &'a Context) -> &'a str {
+    let stem = file_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    context
+        .taxonomy
+        .iter()
+        .map(|folder| (folder, strsim::jaro_winkler(&stem, &folder.to_lowercase())))
+        .filter(|(_, score)| *score >= context.args.taxonomy_threshold)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(folder, _)| folder.as_str())
+        .unwrap_or("unsorted")
+}
+
+/// Buckets a file by how long ago it was last accessed.
+///
+/// Access times are unreliable on `noatime` mounts, so an access time older than the
+/// modification time falls back to the modification time with a one-time warning.
+fn recency_bucket(metadata: &Metadata, context: &Context) -> Option<&'static str> {
+    let modified = metadata.modified().ok()?;
+    let last_used = match metadata.accessed() {
+        Ok(accessed) if accessed >= modified => accessed,
+        _ => {
+            if !context.atime_warned.swap(true, Ordering::Relaxed) {
+                eprintln!("Warning: access times look unreliable on this filesystem, using modified times instead");
+            }
+            modified
+        }
+    };
+
+    let age = SystemTime::now().duration_since(last_used).unwrap_or_default();
+    let days = age.as_secs() / (24 * 60 * 60);
+    Some(match days {
+        0..30 => "active",
+        30..365 => "stale",
+        _ => "cold",
+    })
+}
+
+/// Carries out a planned move, returning a record of it if the file was moved.
+///
+/// With a `staged_path`, the file is copied there first, verified, and then renamed into
+/// place so the destination path never holds a partially written file.
+async fn execute_move(
+    mut planned: PlannedMove,
+    staged_path: Option<PathBuf>,
+    context: &Context,
+) -> Result<Option<MoveRecord>, OrganizeError> {
+    if context.cancelled.load(Ordering::Relaxed) || context.too_many_errors() {
+        return Ok(None);
+    }
+
+    let target_folder = planned.to.parent().unwrap();
+    fs::create_dir_all(target_folder)
+        .await
+        .map_err(|err| OrganizeError::CreateDir(target_folder.to_path_buf(), err))?;
+
+    // Renamed files claim free names up front; every other policy compares against whatever is
+    // at the destination, so that comparison and the move must not interleave with another file's
+    let _destination_guard = match context.args.on_conflict {
+        ConflictPolicy::Rename => None,
+        _ => Some(lock_destination(&planned.to, &context.destination_locks).await),
+    };
+    if !resolve_conflict(&mut planned, context).await? {
+        return Ok(None);
+    }
+
+    let Some(staged_path) = staged_path else {
+        if context.args.copy {
+            transfer::copy_preserving_mtime(&planned.from, &planned.to).await.map_err(|source| OrganizeError::Copy {
+                from: planned.from.clone(),
+                to: planned.to.clone(),
+                source,
+            })?;
+            context.log(|| format!("copied {} -> {}", planned.from.display(), planned.to.display())).await;
+            return Ok(Some(planned.into_record()));
+        }
+        // A followed symlink moves as a link, with a relative target rewritten to stay valid
+        let moved = if planned.from.is_symlink() {
+            symlink::relocate(&planned.from, &planned.to).await
+        } else {
+            transfer::move_file(&planned.from, &planned.to).await
+        };
+        moved.map_err(|source| OrganizeError::Move {
+            from: planned.from.clone(),
+            to: planned.to.clone(),
+            source,
+        })?;
+        context.log(|| format!("moved {} -> {}", planned.from.display(), planned.to.display())).await;
+        return Ok(Some(planned.into_record()));
+    };
+
+    let copied = fs::copy(&planned.from, &staged_path).await.map_err(|source| OrganizeError::Copy {
+        from: planned.from.clone(),
+        to: staged_path.clone(),
+        source,
+    })?;
+    if copied != planned.bytes {
+        let message = format!(
+            "Staged copy of {} is {} bytes, expected {}; leaving the original in place",
+            planned.from.display(),
+            copied,
+            planned.bytes
+        );
+        eprintln!("{}", message);
+        context.log(|| format!("error: {}", message)).await;
+        context.progress.error();
+        let _ = fs::remove_file(&staged_path).await;
+        return Ok(None);
+    }
+
+    if let Ok(modified) = fs::metadata(&planned.from).await.and_then(|metadata| metadata.modified()) {
+        let staged = fs::OpenOptions::new().write(true).open(&staged_path).await;
+        if let Ok(staged) = staged {
+            let _ = staged.into_std().await.set_modified(modified);
+        }
+    }
+
+    transfer::move_file(&staged_path, &planned.to).await.map_err(|source| OrganizeError::Move {
+        from: staged_path.clone(),
+        to: planned.to.clone(),
+        source,
+    })?;
+    if context.args.copy {
+        context.log(|| format!("copied {} -> {} via staging", planned.from.display(), planned.to.display())).await;
+        return Ok(Some(planned.into_record()));
+    }
+    fs::remove_file(&planned.from)
+        .await
+        .map_err(|err| OrganizeError::Remove(planned.from.clone(), err))?;
+    context.log(|| format!("moved {} -> {} via staging", planned.from.display(), planned.to.display())).await;
+    Ok(Some(planned.into_record()))
+}
+
+/// Checks that every recorded move actually happened, returning any discrepancies as an error.
+///
+/// With `copied`, the originals are expected to still be in place.
+async fn verify_moves(records: &[MoveRecord], copied: bool, summary_format: SummaryFormat) -> Result<(), OrganizeError> {
+    let mut discrepancies = Vec::new();
+    for record in records {
+        if !copied && fs::symlink_metadata(&record.from).await.is_ok() {
+            discrepancies.push(format!("{} still exists", record.from.display()));
+        }
+        match fs::metadata(&record.to).await {
+            Ok(metadata) if metadata.len() != record.bytes => discrepancies.push(format!(
+                "{} is {} bytes, expected {}",
+                record.to.display(),
+                metadata.len(),
+                record.bytes
+            )),
+            Ok(_) => {}
+            Err(_) => discrepancies.push(format!("{} is missing", record.to.display())),
+        }
+    }
+
+    if discrepancies.is_empty() {
+        if summary_format == SummaryFormat::Human {
+            println!("Verification passed: {} moves checked.", records.len());
+        }
+        return Ok(());
+    }
+    Err(OrganizeError::Verification { discrepancies, moves: records.len() })
+}
+
+/// Applies the conflict policy when the destination already exists, returning whether the
+/// incoming file should still be moved into place.
+///
+/// With `--on-conflict rename` the destination is changed to a free name instead.
+async fn resolve_conflict(planned: &mut PlannedMove, context: &Context) -> Result<bool, OrganizeError> {
+    if context.args.on_conflict == ConflictPolicy::Rename {
+        let free = claim_free_destination(&planned.from, &planned.to, &context.claimed_destinations);
+        if free != planned.to {
+            context.log(|| format!("conflict at {}: renamed to {}", planned.to.display(), free.display())).await;
+            planned.to = free;
+        }
+        return Ok(true);
+    }
+
+    let Ok(existing) = fs::metadata(&planned.to).await else {
+        return Ok(true);
+    };
+    let incoming = fs::metadata(&planned.from)
+        .await
+        .map_err(|err| OrganizeError::Metadata(planned.from.clone(), err))?;
+
+    let incoming_wins = match context.args.on_conflict {
+        ConflictPolicy::Rename | ConflictPolicy::Overwrite => return Ok(true),
+        ConflictPolicy::Skip => {
+            context.log(|| format!("conflict at {}: skipped {}", planned.to.display(), planned.from.display())).await;
+            return Ok(false);
+        }
+        ConflictPolicy::KeepNewest => incoming.modified().ok() >= existing.modified().ok(),
+        ConflictPolicy::KeepLargest => incoming.len() >= existing.len(),
+    };
+
+    let relative = planned.to.strip_prefix(&context.destination_root).unwrap_or(&planned.to);
+    let trash_path = context.trash_folder.join(relative);
+    if !incoming_wins && context.args.copy {
+        context.log(|| format!("conflict at {}: kept the existing file", planned.to.display())).await;
+        return Ok(false);
+    }
+    let loser = if incoming_wins { &planned.to } else { &planned.from };
+    let trashed = trash::move_to_trash(loser, &trash_path).await.map_err(|source| OrganizeError::Move {
+        from: loser.clone(),
+        to: trash_path.clone(),
+        source,
+    })?;
+    context
+        .log(|| format!("conflict at {}: trashed {} to {}", planned.to.display(), loser.display(), trashed.display()))
+        .await;
+
+    Ok(incoming_wins)
+}
+
+/// Waits for exclusive use of `destination`, held until the returned guard is dropped.
+async fn lock_destination(
+    destination: &Path,
+    locks: &std::sync::Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>,
+) -> tokio::sync::OwnedMutexGuard<()> {
+    let lock = Arc::clone(locks.lock().unwrap().entry(destination.to_path_buf()).or_default());
+    lock.lock_owned().await
+}
+
+/// Claims `destination` for `source`, or the first `name (n).ext` next to it that neither exists
+/// nor was claimed this run for another file.
+fn claim_free_destination(
+    source: &Path,
+    destination: &Path,
+    claimed: &std::sync::Mutex<HashMap<PathBuf, PathBuf>>,
+) -> PathBuf {
+    let mut claimed = claimed.lock().unwrap();
+    let file_name = destination.file_name().unwrap();
+    let free = std::iter::once(destination.to_path_buf())
+        .chain((1..).map(|n| destination.with_file_name(naming::suffixed(file_name, n))))
+        .find(|candidate| claimed.get(candidate).is_none_or(|owner| owner == source) && !candidate.exists())
+        .unwrap();
+    claimed.insert(free.clone(), source.to_path_buf());
+    free
+}
+
+/// Claims `destination` for `source` if it neither exists nor was claimed this run for another file.
+fn claim_destination(source: &Path, destination: &Path, claimed: &std::sync::Mutex<HashMap<PathBuf, PathBuf>>) -> bool {
+    let mut claimed = claimed.lock().unwrap();
+    let free = claimed.get(destination).is_none_or(|owner| owner == source) && !destination.exists();
+    if free {
+        claimed.insert(destination.to_path_buf(), source.to_path_buf());
+    }
+    free
+}
+
+/// Returns the directory component at `index` of the file's path relative to `root`.
+fn source_component(file_path: &Path, root: &Path, index: usize) -> Option<OsString> {
+    let relative = file_path.strip_prefix(root).ok()?;
+    relative
+        .parent()?
+        .components()
+        .nth(index)
+        .map(|component| component.as_os_str().to_os_string())
+}
+
+/// Returns the part of the file name before `delimiter`, if it has one.
+fn file_prefix<'a>(file_path: &'a Path, delimiter: &str) -> Option<&'a str> {
+    let file_name = file_path.file_name()?.to_str()?;
+    let (prefix, _) = file_name.split_once(delimiter)?;
+    (!prefix.is_empty()).then_some(prefix)
+}
+
+/// Collects the name prefixes that are shared by more than one file.
+fn shared_prefixes(files: &[PathBuf], delimiter: &str) -> HashSet<String> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for file in files {
+        if let Some(prefix) = file_prefix(file, delimiter) {
+            *counts.entry(prefix).or_default() += 1;
+        }
+    }
+
+    counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(prefix, _)| prefix.to_string())
+        .collect()
+}
+
+/// Dates a file from the first of its `--date-sources` that is available, reporting which one was used.
+///
+/// `--mode filename-date` tries the file name before any of them. A `--time-source` replaces
+/// them with that timestamp, falling back to the modified time with a warning.
+fn resolve_datetime(file_path: &Path, metadata: &Metadata, context: &Context) -> Option<(NaiveDateTime, DateSource)> {
+    let sources = match context.args.time_source {
+        Some(time_source) => vec![time_source.into(), DateSource::Modified],
+        None => context.args.date_sources.clone(),
+    };
+    let filename_first = (context.args.mode == Mode::FilenameDate).then_some(DateSource::Filename);
+    let sidecar_first = context.args.meta_sidecar.then_some(DateSource::Sidecar);
+    let exif_first = context.args.use_exif.then_some(DateSource::Exif);
+    let resolved = filename_first
+        .into_iter()
+        .chain(sidecar_first)
+        .chain(exif_first)
+        .chain(sources)
+        .find_map(|source| {
+            let datetime = match source {
+                DateSource::Filename => filename_date::parse(file_path, &context.args.filename_pattern),
+                DateSource::Exif => exif_date::capture_datetime(file_path, context.args.use_exif_offset),
+                DateSource::Sidecar => sidecar::timestamp(file_path),
+                DateSource::Created => system_datetime(metadata.created().ok()?),
+                DateSource::Modified => system_datetime(metadata.modified().ok()?),
+                DateSource::Accessed => system_datetime(metadata.accessed().ok()?),
+            };
+            Some((datetime?, source))
+        });
+
+    if let (Some(time_source), Some((_, DateSource::Modified))) = (context.args.time_source, &resolved) {
+        if time_source != TimeSource::Modified && !context.time_source_warned.swap(true, Ordering::Relaxed) {
+            eprintln!(
+                "Warning: {} times are unavailable on this platform, using modified times instead",
+                DateSource::from(time_source).name()
+            );
+        }
+    }
+    resolved
+}
+
+/// Converts a file timestamp to a UTC date and time.
+fn system_datetime(time: SystemTime) -> Option<NaiveDateTime> {
+    #[allow(deprecated)]
+    let datetime = NaiveDateTime::from_timestamp(
+        time.duration_since(std::time::UNIX_EPOCH).ok()?.as_secs() as i64,
+        0,
+    );
+    Some(datetime)
+}
+
+/// Parses the date out of a `week of YYYY-MM-DD` folder name.
+fn week_folder_date(folder: &Path) -> Option<NaiveDate> {
+    let name = folder.file_name()?.to_str()?;
+    let date = name.strip_prefix("week of ")?;
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+/// Whether a folder name is one organize generates: a year, a month, a `YYYY-MM` or day
+/// folder, or a week folder under any `--week-label`.
+///
+/// Names from custom `--year-format`-style templates are not recognized.
+fn is_date_folder_name(name: &str) -> bool {
+    let digits = |value: &str, len: usize| value.len() == len && value.bytes().all(|byte| byte.is_ascii_digit());
+    // Fiscal-year months carry their fiscal number, as in `01-April`
+    let month = match name.split_once('-') {
+        Some((number, month)) if digits(number, 2) => month,
+        _ => name,
+    };
+    digits(name, 4)
+        || month.parse::<chrono::Month>().is_ok()
+        || NaiveDate::parse_from_str(&format!("{}-01", name), "%Y-%m-%d").is_ok()
+        || NaiveDate::parse_from_str(name, "%Y-%m-%d").is_ok()
+        || week_folder_date(Path::new(name)).is_some()
+        || name.strip_prefix("week-").is_some_and(|week| week.parse::<u32>().is_ok())
+        || name.strip_prefix('W').is_some_and(|week| digits(week, 2))
+}
+
+/// How a reverse run treats the tree it flattens.
+#[derive(Clone, Copy)]
+struct ReverseOptions {
+    /// Set modified times from the `week of` folder each file was in.
+    restore_mtime: bool,
+    dry_run: bool,
+    /// Only flatten date folders, moving other folders up whole instead of emptying them.
+    preserve_user_folders: bool,
+    /// What to do when files from different folders share a name.
+    on_conflict: ConflictPolicy,
+}
+
+/// State shared by every folder of a reverse run.
+struct ReverseRun {
+    /// The directory files are moved back into.
+    target_dir: PathBuf,
+    /// Directories walked so far, by canonical path.
+    visited: Mutex<HashSet<PathBuf>>,
+    /// Maps each destination to the file it was claimed for.
+    claimed: std::sync::Mutex<HashMap<PathBuf, PathBuf>>,
+    /// One lock per destination, held from the conflict check until the file is in place.
+    destination_locks: std::sync::Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>,
+    /// Where files that lose a `--on-conflict keep-*` comparison are set aside.
+    trash_folder: PathBuf,
+    /// Set when the run is interrupted, so no more folders are walked and no more files moved.
+    cancelled: Arc<AtomicBool>,
+    /// File name of the `--folder-readme` files, which describe date folders and are deleted with them.
+    folder_readme_name: String,
+    /// Limits how many files are moved at once.
+    permits: Arc<Semaphore>,
+    /// Everything that went wrong, reported once the run is over.
+    failures: std::sync::Mutex<Vec<OrganizeError>>,
+}
+
+impl ReverseRun {
+    fn fail(&self, err: OrganizeError) {
+        self.failures.lock().unwrap().push(err);
+    }
+}
+
+/// Applies the conflict policy to a file moving back to `destination`, returning where it
+/// should go, or `None` to leave it in place.
+///
+/// Except with `rename`, the caller must hold the destination's lock until the file is in place,
+/// so `keep-newest` and `keep-largest` compare against every file that landed before it. Losers
+/// go to the trash under their path relative to the target directory.
+async fn resolve_reverse_conflict(
+    source: &Path,
+    destination: PathBuf,
+    options: ReverseOptions,
+    run: &ReverseRun,
+) -> std::io::Result<Option<PathBuf>> {
+    match options.on_conflict {
+        ConflictPolicy::Rename => return Ok(Some(claim_free_destination(source, &destination, &run.claimed))),
+        ConflictPolicy::Overwrite => return Ok(Some(destination)),
+        ConflictPolicy::Skip | ConflictPolicy::KeepNewest | ConflictPolicy::KeepLargest => {}
+    }
+
+    let existing = fs::metadata(&destination).await.ok();
+    if options.on_conflict == ConflictPolicy::Skip {
+        // A dry run moves nothing, so earlier files only show up as claims
+        let claimed_by_other = {
+            let mut claimed = run.claimed.lock().unwrap();
+            match claimed.get(&destination) {
+                Some(owner) if owner != source => true,
+                _ => {
+                    claimed.insert(destination.clone(), source.to_path_buf());
+                    false
+                }
+            }
+        };
+        return Ok((!claimed_by_other && existing.is_none()).then_some(destination));
+    }
+    let Some(existing) = existing else {
+        return Ok(Some(destination));
+    };
+
+    let incoming = fs::metadata(source).await?;
+    let incoming_wins = match options.on_conflict {
+        ConflictPolicy::KeepNewest => incoming.modified().ok() >= existing.modified().ok(),
+        _ => incoming.len() >= existing.len(),
+    };
+    if options.dry_run {
+        return Ok(incoming_wins.then_some(destination));
+    }
+    let loser = if incoming_wins { destination.as_path() } else { source };
+    let relative = loser.strip_prefix(&run.target_dir).unwrap_or(loser);
+    trash::move_to_trash(loser, &run.trash_folder.join(relative)).await?;
+    Ok(incoming_wins.then_some(destination))
+}
+
+async fn reverse_organize(
+    dir: &str,
+    options: ReverseOptions,
+    permits: Arc<Semaphore>,
+    cancelled: Arc<AtomicBool>,
+    folder_readme_name: String,
+    output: Output,
+    bar: ProgressBar,
+) -> Result<(Summary, Vec<OrganizeError>), OrganizeError> {
+    let run = Arc::new(ReverseRun {
+        target_dir: PathBuf::from(dir),
+        visited: Mutex::new(HashSet::new()),
+        claimed: std::sync::Mutex::new(HashMap::new()),
+        destination_locks: std::sync::Mutex::new(HashMap::new()),
+        trash_folder: trash::run_folder(Path::new(dir), Local::now()),
+        cancelled,
+        folder_readme_name,
+        permits,
+        failures: std::sync::Mutex::new(Vec::new()),
+    });
+    if let Ok(canonical) = fs::canonicalize(dir).await {
+        run.visited.lock().await.insert(canonical);
+    }
+    let mut tasks = Vec::new();
+
+    let read_error = |err| OrganizeError::ReadDir(PathBuf::from(dir), err);
+    let mut entries = fs::read_dir(dir).await.map_err(read_error)?;
+
+    while let Some(entry) = entries.next_entry().await.map_err(read_error)? {
+        let path = entry.path();
+        if path.is_dir() && entry.file_name() != trash::TRASH_DIR {
+            if options.preserve_user_folders && !is_date_folder_name(&entry.file_name().to_string_lossy()) {
+                output.verbose(2, || format!("left {} in place: not a date folder", path.display()));
+                continue;
+            }
+            let run = Arc::clone(&run);
+            let bar = bar.clone();
+            let task = tokio::task::spawn_local(async move { reverse_organize_dir(path, run, options, output, bar).await });
+            tasks.push(task);
+        }
+    }
+
+    let mut summary = Summary::default();
+    for task in tasks {
+        merge_reverse_task(&mut summary, task.await, &run);
+    }
+    let failures = std::mem::take(&mut *run.failures.lock().unwrap());
+    Ok((summary, failures))
+}
+
+/// Moves one file of a reverse run to `destination`, applying the conflict policy, and sets its
+/// modified time to `folder_date` if given.
+async fn reverse_file(
+    path: PathBuf,
+    destination: PathBuf,
+    run: Arc<ReverseRun>,
+    options: ReverseOptions,
+    output: Output,
+    bar: ProgressBar,
+    folder_date: Option<NaiveDate>,
+) -> Result<Summary, OrganizeError> {
+    // Only file moves take a permit, so directories waiting on their children never hold one
+    let _permit = run.permits.acquire().await.unwrap();
+    if run.cancelled.load(Ordering::Relaxed) {
+        bar.inc(1);
+        return Ok(Summary { files_skipped: 1, ..Summary::default() });
+    }
+    let _destination_guard = match options.on_conflict {
+        ConflictPolicy::Rename => None,
+        _ => Some(lock_destination(&destination, &run.destination_locks).await),
+    };
+    let new_file_path = match resolve_reverse_conflict(&path, destination.clone(), options, &run).await {
+        Ok(Some(new_file_path)) => new_file_path,
+        Ok(None) => {
+            bar.inc(1);
+            output.verbose(2, || format!("left {} in place: {} is taken", path.display(), destination.display()));
+            return Ok(Summary { files_skipped: 1, ..Summary::default() });
+        }
+        Err(source) => {
+            bar.inc(1);
+            run.fail(OrganizeError::Move { from: path, to: destination, source });
+            return Ok(Summary { files_failed: 1, ..Summary::default() });
+        }
+    };
+    let bytes = fs::metadata(&path).await.map(|metadata| metadata.len()).unwrap_or(0);
+    if options.dry_run {
+        output.info(|| describe_move("would move", &path, &new_file_path));
+        return Ok(Summary::default());
+    }
+    let moved = transfer::move_file(&path, &new_file_path).await;
+    bar.inc(1);
+    if let Err(source) = moved {
+        run.fail(OrganizeError::Move { from: path, to: new_file_path, source });
+        return Ok(Summary { files_failed: 1, ..Summary::default() });
+    }
+    output.verbose(1, || describe_move("moved", &path, &new_file_path));
+
+    if let Some(date) = folder_date {
+        let timestamp = date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
+        let mtime = FileTime::from_unix_time(timestamp, 0);
+        if let Err(err) = filetime::set_file_mtime(&new_file_path, mtime) {
+            eprintln!("Failed to set modified time of {}: {}", new_file_path.display(), err);
+        }
+    }
+
+    let mut summary = Summary::default();
+    summary.record_move(bytes, None);
+    Ok(summary)
+}
+
+/// Moves the files of the user folder `source` into `destination`, which already exists, one at a
+/// time so each one that collides goes through `--on-conflict`. The folders it empties are removed.
+async fn merge_user_folder(
+    source: PathBuf,
+    destination: PathBuf,
+    run: &Arc<ReverseRun>,
+    options: ReverseOptions,
+    output: Output,
+    bar: &ProgressBar,
+) -> Summary {
+    let mut summary = Summary::default();
+    let mut tasks = Vec::new();
+    let mut folders = Vec::new();
+    let mut pending = vec![source.clone()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = match fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(err) => {
+                run.fail(OrganizeError::ReadDir(dir, err));
+                summary.files_failed += 1;
+                continue;
+            }
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if entry.file_type().await.is_ok_and(|file_type| file_type.is_dir()) {
+                pending.push(path);
+                continue;
+            }
+            let new_path = destination.join(path.strip_prefix(&source).unwrap());
+            if !options.dry_run {
+                let parent = new_path.parent().unwrap();
+                if let Err(err) = fs::create_dir_all(parent).await {
+                    run.fail(OrganizeError::CreateDir(parent.to_path_buf(), err));
+                    summary.files_failed += 1;
+                    continue;
+                }
+            }
+            bar.inc_length(1);
+            let task = reverse_file(path, new_path, Arc::clone(run), options, output, bar.clone(), None);
+            tasks.push(tokio::task::spawn_local(task));
+        }
+        folders.push(dir);
+    }
+
+    for task in tasks {
+        merge_reverse_task(&mut summary, task.await, run);
+    }
+    // Subfolders were found after their parents, so removing in reverse goes bottom-up
+    if !options.dry_run {
+        for folder in folders.iter().rev() {
+            let _ = fs::remove_dir(folder).await;
+        }
+    }
+    summary
+}
+
+/// Adds a finished reverse task to `summary`, counting a folder that couldn't be read, or a
+/// task that panicked, as a failure.
+fn merge_reverse_task(
+    summary: &mut Summary,
+    joined: Result<Result<Summary, OrganizeError>, tokio::task::JoinError>,
+    run: &ReverseRun,
+) {
+    match joined.map_err(OrganizeError::Task).and_then(|result| result) {
+        Ok(task_summary) => summary.merge(task_summary),
+        Err(err) => {
+            run.fail(err);
+            summary.files_failed += 1;
+        }
+    }
+}
+
+async fn reverse_organize_dir(
+    current_dir: PathBuf,
+    run: Arc<ReverseRun>,
+    options: ReverseOptions,
+    output: Output,
+    bar: ProgressBar,
+) -> Result<Summary, OrganizeError> {
+    let read_error = |err| OrganizeError::ReadDir(current_dir.clone(), err);
+
+    // Symlinked directories can point back at an ancestor, so never walk the same directory twice
+    if run.cancelled.load(Ordering::Relaxed) {
+        return Ok(Summary::default());
+    }
+    let canonical = fs::canonicalize(&current_dir).await.map_err(read_error)?;
+    if !run.visited.lock().await.insert(canonical) {
+        return Ok(Summary::default());
+    }
+
+    let mut entries = fs::read_dir(&current_dir).await.map_err(read_error)?;
+
+    let mut summary = Summary::default();
+    let mut tasks = Vec::new();
+    let folder_date = if options.restore_mtime { week_folder_date(&current_dir) } else { None };
+    let mut was_empty = true;
+
+    while !run.cancelled.load(Ordering::Relaxed) {
+        // Files already handed to tasks still need collecting, so a failed read ends the walk of this folder only
+        let entry = match entries.next_entry().await {
+            Ok(Some(entry)) => entry,
+            Ok(None) => break,
+            Err(err) => {
+                run.fail(read_error(err));
+                summary.files_failed += 1;
+                break;
+            }
+        };
+        was_empty = false;
+        let path = entry.path();
+        if path.is_file() && is_folder_readme(&path, &run.folder_readme_name) {
+            // Moving it back would leave a stale description in the target directory
+            if options.dry_run {
+                output.info(|| format!("would remove {}", path.display()));
+            } else if let Err(err) = fs::remove_file(&path).await {
+                run.fail(OrganizeError::Remove(path, err));
+                summary.files_failed += 1;
+            }
+        } else if path.is_file() {
+            // The total grows as the walk finds files
+            bar.inc_length(1);
+            let destination = run.target_dir.join(path.file_name().unwrap());
+            let task = reverse_file(path, destination, Arc::clone(&run), options, output, bar.clone(), folder_date);
+            tasks.push(tokio::task::spawn_local(task));
+        } else if path.is_dir() && options.preserve_user_folders && !is_date_folder_name(&entry.file_name().to_string_lossy()) {
+            // A folder of the user's own inside a date folder moves up whole, keeping its layout
+            let new_dir_path = run.target_dir.join(entry.file_name());
+            let _folder_guard = lock_destination(&new_dir_path, &run.destination_locks).await;
+            if fs::symlink_metadata(&new_dir_path).await.is_ok() {
+                summary.merge(merge_user_folder(path, new_dir_path, &run, options, output, &bar).await);
+            } else if options.dry_run {
+                output.info(|| describe_move("would move", &path, &new_dir_path));
+            } else if let Err(source) = fs::rename(&path, &new_dir_path).await {
+                run.fail(OrganizeError::Move { from: path, to: new_dir_path, source });
+                summary.files_failed += 1;
+            } else {
+                output.verbose(1, || describe_move("moved", &path, &new_dir_path));
+            }
+        } else if path.is_dir() {
+            let run = Arc::clone(&run);
+            let bar = bar.clone();
+            let task = tokio::task::spawn_local(async move { reverse_organize_dir(path, run, options, output, bar).await });
+            tasks.push(task);
+        }
+    }
+
+    for task in tasks {
+        merge_reverse_task(&mut summary, task.await, &run);
+    }
+
+    // Every subfolder has been emptied by now, so removing the empty ones here cleans the tree bottom-up.
+    // A folder that was already empty isn't one this run left behind, so it stays.
+    if !options.dry_run && !was_empty {
+        let _ = fs::remove_dir(&current_dir).await;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn week_folder(datetime: &NaiveDateTime, week_start: &str) -> PathBuf {
+        let args = Cli::parse_from(["organize", "dir", "--week-start", week_start]);
+        date_folder(datetime, Path::new("dir"), &args.date_layout(), &mut Trace::off()).0
+    }
+
+    #[test]
+    fn monday_lands_in_week_for_each_week_start() {
+        let monday = NaiveDate::from_ymd_opt(2024, 3, 18).unwrap().and_hms_opt(12, 0, 0).unwrap();
+
+        assert_eq!(week_folder(&monday, "sunday"), Path::new("dir/2024/March/week of 2024-03-17"));
+        assert_eq!(week_folder(&monday, "monday"), Path::new("dir/2024/March/week of 2024-03-18"));
+    }
+
+    #[test]
+    fn ordinal_weeks_follow_the_week_start() {
+        let folder = |(year, month, day), week_start| {
+            let args = Cli::parse_from(["organize", "dir", "--week-label", "ordinal", "--week-start", week_start]);
+            let datetime = NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(12, 0, 0).unwrap();
+            date_folder(&datetime, Path::new("dir"), &args.date_layout(), &mut Trace::off()).0
+        };
+
+        // March 2024 starts on a Friday, so Sunday the 3rd begins week 2 only when weeks start on Sunday
+        assert_eq!(folder((2024, 3, 3), "sunday"), Path::new("dir/2024/March/week-2"));
+        assert_eq!(folder((2024, 3, 3), "monday"), Path::new("dir/2024/March/week-1"));
+        assert_eq!(folder((2024, 3, 4), "sunday"), Path::new("dir/2024/March/week-2"));
+        assert_eq!(folder((2024, 3, 4), "monday"), Path::new("dir/2024/March/week-2"));
+    }
+
+    #[test]
+    fn iso_week_numbers_are_filed_under_their_week_based_year() {
+        let folder = |(year, month, day)| {
+            let args = Cli::parse_from(["organize", "dir", "--week-label", "number", "--week-system", "iso"]);
+            let datetime = NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(12, 0, 0).unwrap();
+            date_folder(&datetime, Path::new("dir"), &args.date_layout(), &mut Trace::off()).0
+        };
+
+        assert_eq!(folder((2024, 12, 30)), Path::new("dir/2025/January/W01"));
+        assert_eq!(folder((2021, 1, 1)), Path::new("dir/2020/December/W53"));
+        assert_eq!(folder((2024, 3, 18)), Path::new("dir/2024/March/W12"));
+    }
+
+    /// Describes what the filters built from `extra_args` and the rules CSV `rules_csv` decide for `file`.
+    fn decide(extra_args: &[&str], rules_csv: &str, file: &str) -> String {
+        static CSV_COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+        let args = Cli::parse_from(["organize", "dir"].iter().chain(extra_args));
+        let rules = if rules_csv.is_empty() {
+            Vec::new()
+        } else {
+            let count = CSV_COUNT.fetch_add(1, Ordering::Relaxed);
+            let path = std::env::temp_dir().join(format!("organize-rules-test-{}-{}.csv", std::process::id(), count));
+            std::fs::write(&path, format!("pattern,destination_template\n{}", rules_csv)).unwrap();
+            let rules = rules::load_csv(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            rules
+        };
+        let filters = Filters::new(&args, rules).unwrap();
+        match filters.should_process(Path::new(file)) {
+            Decision::Process(Route::Rule(rule)) => format!("rule {}", rule.pattern()),
+            Decision::Process(Route::Mode(mode)) => format!("mode {}", mode.name()),
+            Decision::Skip(reason) => format!("skip: {}", reason),
+        }
+    }
+
+    #[test]
+    fn files_are_processed_by_mode_without_filters() {
+        assert_eq!(decide(&[], "", "a.txt"), "mode date");
+        assert_eq!(decide(&["--mode", "extension"], "", "noext"), "mode extension");
+    }
+
+    #[test]
+    fn exclude_wins_over_include() {
+        let args = ["--include", "jpg", "--exclude", "*.jpg"];
+        assert_eq!(decide(&args, "", "a.jpg"), "skip: matches --exclude");
+    }
+
+    #[test]
+    fn include_ignores_case_and_skips_extensionless_files() {
+        assert_eq!(decide(&["--include", "jpg"], "", "A.JPG"), "mode date");
+        assert_eq!(decide(&["--include", ".JPG"], "", "a.jpg"), "mode date");
+        assert_eq!(decide(&["--include", "jpg"], "", "noext"), "skip: extension `` is not in --include");
+        assert_eq!(decide(&["--include", "jpg"], "", "a.png"), "skip: extension `png` is not in --include");
+    }
+
+    #[test]
+    fn include_and_only_ext_dated_must_both_allow_a_file() {
+        let args = ["--include", "jpg,png", "--only-ext-dated", "png"];
+        assert_eq!(decide(&args, "", "a.png"), "mode date");
+        assert_eq!(decide(&args, "", "a.jpg"), "skip: extension `jpg` is not in --only-ext-dated");
+        assert_eq!(decide(&args, "", "a.gif"), "skip: extension `gif` is not in --include");
+    }
+
+    #[test]
+    fn rules_route_only_files_the_filters_keep() {
+        let rules = "*.pdf,docs/%Y\n*,other/%Y";
+        assert_eq!(decide(&[], rules, "a.pdf"), "rule *.pdf");
+        assert_eq!(decide(&[], rules, "a.jpg"), "rule *");
+        assert_eq!(decide(&["--include", "jpg"], rules, "a.pdf"), "skip: extension `pdf` is not in --include");
+        assert_eq!(decide(&["--exclude", "*.pdf"], rules, "a.pdf"), "skip: matches --exclude");
+        assert_eq!(decide(&["--include", "jpg"], rules, "a.jpg"), "rule *");
+    }
+
+    #[test]
+    fn rules_csv_rejects_templates_that_cannot_be_rendered() {
+        let path = std::env::temp_dir().join(format!("organize-rules-render-test-{}.csv", std::process::id()));
+        std::fs::write(&path, "pattern,destination_template\n*,docs/%Z").unwrap();
+        let err = rules::load_csv(&path).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(err.contains("line 2"), "{}", err);
+    }
+
+    #[test]
+    fn rules_csv_rejects_templates_outside_the_target_directory() {
+        let path = std::env::temp_dir().join(format!("organize-rules-escape-test-{}.csv", std::process::id()));
+        for template in ["../%Y", "docs/../../%Y", "/tmp/%Y"] {
+            std::fs::write(&path, format!("pattern,destination_template\n*.pdf,docs/%Y\n*,{}", template)).unwrap();
+            let err = rules::load_csv(&path).err().unwrap();
+            assert!(err.contains("line 3"), "{}", err);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Creates a file at `path` whose modified time is midnight on `date`.
+    fn write_dated(path: &Path, (year, month, day): (i32, u32, u32)) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, path.to_string_lossy().as_bytes()).unwrap();
+        let timestamp = NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        filetime::set_file_mtime(path, FileTime::from_unix_time(timestamp.and_utc().timestamp(), 0)).unwrap();
+    }
+
+    /// Reads every file under `dir` except the stats file, sorted by contents.
+    fn contents_under(dir: &Path) -> Vec<String> {
+        let mut contents = Vec::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else if path.file_name().unwrap() != summary::STATS_FILE {
+                    contents.push(std::fs::read_to_string(&path).unwrap());
+                }
+            }
+        }
+        contents.sort();
+        contents
+    }
+
+    /// Writes `x.txt` into a subfolder of `dir` per entry, all dated 2024-03-13 at the given hour.
+    fn write_colliding(dir: &Path, files: &[(&str, u32)]) {
+        for (index, (contents, hour)) in files.iter().enumerate() {
+            let path = dir.join(format!("d{}", index)).join("x.txt");
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, contents).unwrap();
+            let timestamp = NaiveDate::from_ymd_opt(2024, 3, 13).unwrap().and_hms_opt(*hour, 0, 0).unwrap();
+            filetime::set_file_mtime(&path, FileTime::from_unix_time(timestamp.and_utc().timestamp(), 0)).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn conflict_policies_keep_every_colliding_file() {
+        for policy in ["rename", "skip", "keep-newest", "keep-largest"] {
+            let dir = std::env::temp_dir().join(format!("organize-conflict-test-{}-{}", std::process::id(), policy));
+            // `ccc` is the newest and `eeee` the largest
+            write_colliding(&dir, &[("a", 1), ("bb", 2), ("ccc", 4), ("eeee", 3)]);
+
+            let dir_arg = dir.to_str().unwrap();
+            let args = ["organize", dir_arg, "--recursive", "--on-conflict", policy, "--jobs", "8", "--summary-format", "none"];
+            LocalSet::new().run_until(organize(Cli::parse_from(args))).await.unwrap();
+
+            let kept = std::fs::read_to_string(dir.join("2024/March/week of 2024-03-10/x.txt")).unwrap();
+            let contents = contents_under(&dir);
+            std::fs::remove_dir_all(&dir).unwrap();
+            assert_eq!(contents, ["a", "bb", "ccc", "eeee"], "files lost with --on-conflict {}", policy);
+            match policy {
+                "keep-newest" => assert_eq!(kept, "ccc"),
+                "keep-largest" => assert_eq!(kept, "eeee"),
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn dry_run_script_uses_the_renamed_destinations() {
+        let dir = std::env::temp_dir().join(format!("organize-plan-rename-test-{}", std::process::id()));
+        write_colliding(&dir, &[("a", 1), ("bb", 2)]);
+        let script = dir.with_extension("sh");
+
+        let dir_arg = dir.to_str().unwrap();
+        let args = ["organize", dir_arg, "--recursive", "--dry-run", "--emit-script", script.to_str().unwrap()];
+        LocalSet::new().run_until(organize(Cli::parse_from(args))).await.unwrap();
+
+        let contents = std::fs::read_to_string(&script).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&script).unwrap();
+        assert!(contents.contains("week of 2024-03-10/x.txt'"), "{}", contents);
+        assert!(contents.contains("week of 2024-03-10/x (1).txt'"), "{}", contents);
+    }
+
+    #[test]
+    fn shell_quote_escapes_quotes_spaces_and_dashes() {
+        let quote = |path: &str| String::from_utf8(script::shell_quote(Path::new(path))).unwrap();
+
+        assert_eq!(quote("plain.txt"), "'plain.txt'");
+        assert_eq!(quote("my file.txt"), "'my file.txt'");
+        assert_eq!(quote("it's.txt"), "'it'\\''s.txt'");
+        assert_eq!(quote("-rf"), "'-rf'");
+        assert_eq!(quote("$HOME `x` \"y\""), "'$HOME `x` \"y\"'");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shell_quote_round_trips_through_sh() {
+        for name in ["my file.txt", "it's.txt", "-n", "'';$(echo)", "tab\there"] {
+            let mut command = b"printf %s ".to_vec();
+            command.extend(script::shell_quote(Path::new(name)));
+            let output = std::process::Command::new("sh").arg("-c").arg(String::from_utf8(command).unwrap()).output().unwrap();
+            assert_eq!(String::from_utf8(output.stdout).unwrap(), name);
+        }
+    }
+
+    #[tokio::test]
+    async fn log_is_written_when_a_run_stops_early() {
+        let dir = std::env::temp_dir().join(format!("organize-log-test-{}", std::process::id()));
+        write_dated(&dir.join("a.txt"), (2024, 3, 13));
+        let log_path = dir.with_extension("log");
+
+        let dir_arg = dir.to_str().unwrap();
+        let args = ["organize", dir_arg, "--dry-run", "--log-file", log_path.to_str().unwrap()];
+        LocalSet::new().run_until(organize(Cli::parse_from(args))).await.unwrap();
+
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&log_path).unwrap();
+        assert!(log.contains("organizing"), "{:?}", log);
+    }
+
+    #[tokio::test]
+    async fn sidecars_move_with_the_file_they_describe() {
+        let dir = std::env::temp_dir().join(format!("organize-sidecar-test-{}", std::process::id()));
+        write_dated(&dir.join("a.txt"), (2024, 3, 13));
+        std::fs::write(dir.join("a.txt.meta"), r#"{"timestamp": "2022-05-05 10:00:00"}"#).unwrap();
+
+        let args = ["organize", dir.to_str().unwrap(), "--meta-sidecar"];
+        LocalSet::new().run_until(organize(Cli::parse_from(args))).await.unwrap();
+
+        let week = dir.join("2022/May/week of 2022-05-01");
+        let moved = (week.join("a.txt").is_file(), week.join("a.txt.meta").is_file());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(moved, (true, true));
+    }
+
+    #[tokio::test]
+    async fn copying_twice_leaves_the_copies_alone() {
+        let dir = std::env::temp_dir().join(format!("organize-copy-twice-test-{}", std::process::id()));
+        let output = dir.with_extension("out");
+        write_dated(&dir.join("a.txt"), (2024, 3, 13));
+
+        let (dir_arg, output_arg) = (dir.to_str().unwrap(), output.to_str().unwrap());
+        for _ in 0..2 {
+            let args = ["organize", dir_arg, "--copy", "--output", output_arg];
+            LocalSet::new().run_until(organize(Cli::parse_from(args))).await.unwrap();
+        }
+
+        let copies = contents_under(&output);
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_dir_all(&output).unwrap();
+        assert_eq!(copies.len(), 1, "{:?}", copies);
+    }
+
+    #[tokio::test]
+    async fn max_errors_keeps_the_failures_instead_of_retrying() {
+        let dir = std::env::temp_dir().join(format!("organize-max-errors-test-{}", std::process::id()));
+        write_dated(&dir.join("a.txt"), (2024, 3, 13));
+        write_dated(&dir.join("b.txt"), (2024, 3, 14));
+        // A file where the year folder belongs makes every move into it fail
+        std::fs::write(dir.join("2024"), "").unwrap();
+
+        let dir_arg = dir.to_str().unwrap();
+        let args = ["organize", dir_arg, "--exclude", "2024", "--jobs", "1", "--max-errors", "1", "--retry-failed-pass"];
+        let err = LocalSet::new().run_until(organize(Cli::parse_from(args))).await.unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(&err, OrganizeError::Failed(failures) if failures.len() == 1), "{}", err);
+        assert_eq!(err.exit_code(), 1);
+    }
+
+    #[tokio::test]
+    async fn name_filters_run_before_size_date_and_content_filters() {
+        let dir = std::env::temp_dir().join(format!("organize-filter-order-test-{}", std::process::id()));
+        let log_path = dir.with_extension("log");
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, contents: String, (year, month, day)| {
+            let path = dir.join(name);
+            std::fs::write(&path, contents).unwrap();
+            let timestamp = NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(0, 0, 0).unwrap();
+            filetime::set_file_mtime(&path, FileTime::from_unix_time(timestamp.and_utc().timestamp(), 0)).unwrap();
+        };
+        let large = |text: &str| format!("{}{}", text, " ".repeat(200));
+        // Each file fails every filter from its own onwards, so the reason logged is the first that applies
+        write("excluded.txt", "small".to_string(), (2020, 1, 1));
+        write("not-included.png", "small".to_string(), (2020, 1, 1));
+        write("small.txt", "small".to_string(), (2020, 1, 1));
+        write("old.txt", large("haystack"), (2020, 1, 1));
+        write("unmatched.txt", large("haystack"), (2024, 3, 13));
+        write("matched.txt", large("needle"), (2024, 3, 13));
+
+        let dir_arg = dir.to_str().unwrap();
+        let args = [
+            "organize", dir_arg, "--dry-run", "--log-file", log_path.to_str().unwrap(),
+            "--include", "txt", "--exclude", "excluded.*", "--min-size", "100",
+            "--since", "2024-01-01", "--content-match", "needle",
+        ];
+        LocalSet::new().run_until(organize(Cli::parse_from(args))).await.unwrap();
+
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&log_path).unwrap();
+        let reason = |name: &str| {
+            let prefix = format!("skipped {}: ", dir.join(name).display());
+            log.lines().find_map(|line| line.split_once(&prefix).map(|(_, reason)| reason.to_string()))
+        };
+        assert_eq!(reason("excluded.txt").as_deref(), Some("matches --exclude"));
+        assert_eq!(reason("not-included.png").as_deref(), Some("extension `png` is not in --include"));
+        assert_eq!(reason("small.txt").as_deref(), Some("5 bytes, under --min-size"));
+        assert_eq!(reason("old.txt").as_deref(), Some("dated 2020-01-01, outside --since/--until"));
+        assert_eq!(reason("unmatched.txt").as_deref(), Some("content does not match `needle`"));
+        assert_eq!(reason("matched.txt"), None);
+    }
+
+    #[tokio::test]
+    async fn log_lines_are_readable_while_the_run_goes_on() {
+        let path = std::env::temp_dir().join(format!("organize-log-follow-test-{}.log", std::process::id()));
+        let log = LogFile::open(&path, None).await.unwrap();
+        log.write("progress: 3 of 10 files").await;
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        drop(log);
+        std::fs::remove_file(&path).unwrap();
+        assert!(contents.ends_with("progress: 3 of 10 files\n"), "{:?}", contents);
+    }
+
+    #[tokio::test]
+    async fn global_collision_detection_leaves_organized_files_alone() {
+        let dir = std::env::temp_dir().join(format!("organize-global-names-test-{}", std::process::id()));
+        write_dated(&dir.join("a.txt"), (2024, 3, 13));
+
+        let dir_arg = dir.to_str().unwrap();
+        LocalSet::new()
+            .run_until(async {
+                organize(Cli::parse_from(["organize", dir_arg, "--summary-format", "none"])).await.unwrap();
+                let args = ["organize", dir_arg, "--recursive", "--rename-collision-detect-global", "--summary-format", "none"];
+                organize(Cli::parse_from(args)).await.unwrap();
+            })
+            .await;
+
+        let in_place = dir.join("2024/March/week of 2024-03-10/a.txt").is_file();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(in_place);
+    }
+
+    #[tokio::test]
+    async fn files_left_in_place_claim_no_global_names() {
+        let dir = std::env::temp_dir().join(format!("organize-unclaimed-names-test-{}", std::process::id()));
+        let (main_root, camera_root) = (dir.join("main"), dir.join("camera"));
+        write_dated(&main_root.join("x.txt"), (2020, 1, 1));
+        write_dated(&camera_root.join("x.txt"), (2024, 3, 13));
+
+        let root_arg = format!("camera={}", camera_root.display());
+        let args = [
+            "organize",
+            main_root.to_str().unwrap(),
+            "--root",
+            &root_arg,
+            "--since",
+            "2024-01-01",
+            "--rename-collision-detect-global",
+            "--summary-format",
+            "none",
+        ];
+        LocalSet::new().run_until(organize(Cli::parse_from(args))).await.unwrap();
+
+        let kept_name = main_root.join("camera/2024/March/week of 2024-03-10/x.txt").is_file();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(kept_name);
+    }
+
+    #[tokio::test]
+    async fn date_folders_are_rooted_at_the_target_dir() {
+        let dir = std::env::temp_dir().join(format!("organize-root-test-{}", std::process::id()));
+        write_dated(&dir.join("a.txt"), (2024, 3, 13));
+        write_dated(&dir.join("nested/deeper/b.jpg"), (2023, 12, 1));
+
+        let dir_arg = dir.to_str().unwrap();
+        LocalSet::new()
+            .run_until(organize(Cli::parse_from(["organize", dir_arg, "--recursive", "--summary-format", "none"])))
+            .await
+            .unwrap();
+
+        let a = dir.join("2024/March/week of 2024-03-10/a.txt");
+        let b = dir.join("2023/December/week of 2023-11-26/b.jpg");
+        let nested_year = dir.join("nested/deeper/2023");
+        let (a_exists, b_exists, nested_exists) = (a.is_file(), b.is_file(), nested_year.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(a_exists && b_exists);
+        assert!(!nested_exists);
+    }
+
+    /// Reverses `dir` with `options`, four moves at a time.
+    async fn reverse(dir: &str, options: ReverseOptions) -> Summary {
+        let (permits, output) = (Arc::new(Semaphore::new(4)), Output::new(true, 0));
+        let readme_name = "README.txt".to_string();
+        reverse_organize(dir, options, permits, Arc::default(), readme_name, output, ProgressBar::hidden())
+            .await
+            .unwrap()
+            .0
+    }
+
+    #[tokio::test]
+    async fn reverse_returns_the_moves_that_failed() {
+        let dir = std::env::temp_dir().join(format!("organize-reverse-failure-test-{}", std::process::id()));
+        write_dated(&dir.join("2024/March/week of 2024-03-10/a.txt"), (2024, 3, 13));
+        // A folder can't be overwritten by a file
+        write_dated(&dir.join("a.txt/notes.txt"), (2024, 3, 13));
+
+        let options = ReverseOptions {
+            restore_mtime: false,
+            dry_run: false,
+            preserve_user_folders: true,
+            on_conflict: ConflictPolicy::Overwrite,
+        };
+        let (permits, output) = (Arc::new(Semaphore::new(4)), Output::new(true, 0));
+        let readme_name = "README.txt".to_string();
+        let dir_arg = dir.to_str().unwrap();
+        let run = reverse_organize(dir_arg, options, permits, Arc::default(), readme_name, output, ProgressBar::hidden());
+        let reversed = LocalSet::new().run_until(run).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+        let (summary, failures) = reversed.unwrap();
+        assert_eq!(summary.files_failed, 1);
+        assert!(matches!(failures.as_slice(), [OrganizeError::Move { .. }]), "{:?}", failures.len());
+    }
+
+    #[tokio::test]
+    async fn reverse_leaves_only_the_original_files() {
+        let dir = std::env::temp_dir().join(format!("organize-reverse-test-{}", std::process::id()));
+        write_dated(&dir.join("a.txt"), (2024, 3, 13));
+        write_dated(&dir.join("b.jpg"), (2023, 12, 1));
+        write_dated(&dir.join("README.txt"), (2022, 6, 1));
+
+        let dir_arg = dir.to_str().unwrap();
+        LocalSet::new()
+            .run_until(async {
+                organize(Cli::parse_from(["organize", dir_arg, "--folder-readme", "--summary-format", "none"])).await.unwrap();
+                let readme = dir.join("2024/March/week of 2024-03-10/README.txt");
+                assert!(folder_readme::is_folder_readme(&readme));
+                // The READMEs describe the folders they're in, so a later run leaves them there
+                organize(Cli::parse_from(["organize", dir_arg, "--recursive", "--summary-format", "none"])).await.unwrap();
+                assert!(readme.is_file());
+                std::fs::create_dir(dir.join("drafts")).unwrap();
+                let options = ReverseOptions {
+                    restore_mtime: false,
+                    dry_run: false,
+                    preserve_user_folders: false,
+                    on_conflict: ConflictPolicy::Rename,
+                };
+                reverse(dir_arg, options).await;
+            })
+            .await;
+
+        let mut entries: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        entries.sort();
+        let user_readme_kept = !folder_readme::is_folder_readme(&dir.join("README.txt"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(entries, [summary::STATS_FILE, "README.txt", "a.txt", "b.jpg", "drafts"]);
+        assert!(user_readme_kept);
+    }
+
+    #[tokio::test]
+    async fn reverse_merges_user_folders_into_existing_ones() {
+        let dir = std::env::temp_dir().join(format!("organize-reverse-merge-test-{}", std::process::id()));
+        write_dated(&dir.join("Project/notes.txt"), (2024, 1, 1));
+        write_dated(&dir.join("2024/March/week of 2024-03-10/Project/notes.txt"), (2024, 3, 13));
+        write_dated(&dir.join("2024/March/week of 2024-03-10/Project/drafts/plan.txt"), (2024, 3, 13));
+        write_dated(&dir.join("2023/December/week of 2023-11-26/Project/notes.txt"), (2023, 12, 1));
+
+        let options = ReverseOptions {
+            restore_mtime: false,
+            dry_run: false,
+            preserve_user_folders: true,
+            on_conflict: ConflictPolicy::Rename,
+        };
+        let summary = LocalSet::new().run_until(reverse(dir.to_str().unwrap(), options)).await;
+
+        let contents = contents_under(&dir);
+        let entries: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        let plan_moved = dir.join("Project/drafts/plan.txt").is_file();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(summary.files_failed, 0);
+        assert_eq!(contents.len(), 4);
+        assert_eq!(entries, ["Project"]);
+        assert!(plan_moved);
+    }
+
+    #[tokio::test]
+    async fn reverse_keeps_files_that_share_a_name() {
+        let dir = std::env::temp_dir().join(format!("organize-reverse-collision-test-{}", std::process::id()));
+        let write_report = |contents: &str, (year, month, day)| {
+            let path = dir.join("report.txt");
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(&path, contents).unwrap();
+            let timestamp = NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(0, 0, 0).unwrap();
+            filetime::set_file_mtime(&path, FileTime::from_unix_time(timestamp.and_utc().timestamp(), 0)).unwrap();
+        };
+
+        let dir_arg = dir.to_str().unwrap();
+        LocalSet::new()
+            .run_until(async {
+                let args = ["organize", dir_arg, "--summary-format", "none"];
+                write_report("march", (2024, 3, 13));
+                organize(Cli::parse_from(args)).await.unwrap();
+                write_report("december", (2023, 12, 1));
+                organize(Cli::parse_from(args)).await.unwrap();
+                assert!(dir.join("2024/March/week of 2024-03-10/report.txt").is_file());
+                assert!(dir.join("2023/December/week of 2023-11-26/report.txt").is_file());
+
+                let options = ReverseOptions {
+                    restore_mtime: false,
+                    dry_run: false,
+                    preserve_user_folders: false,
+                    on_conflict: ConflictPolicy::Rename,
+                };
+                reverse(dir_arg, options).await;
+            })
+            .await;
+
+        let mut contents = vec![
+            std::fs::read_to_string(dir.join("report.txt")).unwrap(),
+            std::fs::read_to_string(dir.join("report (1).txt")).unwrap(),
+        ];
+        contents.sort();
+        let leftover = dir.join("2024").exists() || dir.join("2023").exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(contents, ["december", "march"]);
+        assert!(!leftover);
+
+        for policy in [ConflictPolicy::KeepNewest, ConflictPolicy::KeepLargest] {
+            // `ccc` is the newest and `eeee` the largest
+            write_colliding(&dir, &[("a", 1), ("bb", 2), ("ccc", 4), ("eeee", 3)]);
+            LocalSet::new()
+                .run_until(async {
+                    let options = ReverseOptions {
+                        restore_mtime: false,
+                        dry_run: false,
+                        preserve_user_folders: false,
+                        on_conflict: policy,
+                    };
+                    reverse(dir_arg, options).await;
+                })
+                .await;
+
+            let kept = std::fs::read_to_string(dir.join("x.txt")).unwrap();
+            let contents = contents_under(&dir);
+            std::fs::remove_dir_all(&dir).unwrap();
+            assert_eq!(contents, ["a", "bb", "ccc", "eeee"]);
+            assert_eq!(kept, if policy == ConflictPolicy::KeepNewest { "ccc" } else { "eeee" });
+        }
     }
 }
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// One move recorded in a `--manifest`.
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// Writes the moves as a JSON array of `{"from", "to"}` objects.
+///
+/// Paths are made absolute first, so `--undo` works from any working directory.
+///
+/// The manifest is written to a temporary file next to `path` and renamed into place, so an
+/// interrupted write never leaves a truncated manifest behind.
+pub async fn write<'a>(path: &Path, moves: impl Iterator<Item = (&'a Path, &'a Path)>) -> io::Result<()> {
+    let entries = moves
+        .map(|(from, to)| {
+            Ok(Entry {
+                from: absolute(from)?,
+                to: absolute(to)?,
+            })
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    let contents = serde_json::to_string_pretty(&entries).map_err(io::Error::other)?;
+
+    let mut temporary = path.as_os_str().to_os_string();
+    temporary.push(".tmp");
+    fs::write(&temporary, contents).await?;
+    fs::rename(&temporary, path).await
+}
+
+/// Resolves `path` against the working directory and its parent's symlinks. The file itself
+/// may be gone, as the source of a move is.
+fn absolute(path: &Path) -> io::Result<PathBuf> {
+    let path = std::path::absolute(path)?;
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => match std::fs::canonicalize(parent) {
+            Ok(parent) => Ok(parent.join(name)),
+            Err(_) => Ok(path),
+        },
+        _ => Ok(path),
+    }
+}
+
+/// Reads the moves recorded in a manifest written by [`write`].
+pub async fn read(path: &Path) -> io::Result<Vec<Entry>> {
+    let contents = fs::read_to_string(path).await?;
+    serde_json::from_str(&contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
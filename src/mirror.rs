@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Folder for files that have no same-named file in the reference tree.
+pub const UNMATCHED_DIR: &str = "unmatched";
+
+/// Maps each file name in the reference tree to the folder it is in, relative to `reference`.
+///
+/// When a name appears more than once, the shallowest folder wins, then the first by name.
+pub fn index(reference: &Path) -> io::Result<HashMap<OsString, PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        let entries = std::fs::read_dir(reference.join(&relative))?;
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() && !entry.file_name().to_string_lossy().starts_with('.') {
+                pending.push(relative.join(entry.file_name()));
+            } else if file_type.is_file() {
+                files.push((relative.clone(), entry.file_name()));
+            }
+        }
+    }
+
+    files.sort_by(|(a, _), (b, _)| a.components().count().cmp(&b.components().count()).then_with(|| a.cmp(b)));
+    let mut folders = HashMap::new();
+    for (folder, name) in files {
+        folders.entry(name).or_insert(folder);
+    }
+    Ok(folders)
+}
@@ -0,0 +1,60 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+/// File names in use, each with the files that hold it.
+pub type TakenNames = HashMap<OsString, HashSet<PathBuf>>;
+
+/// Inserts ` (n)` before the extension, turning `file.txt` into `file (1).txt`.
+pub fn suffixed(file_name: &OsStr, n: usize) -> OsString {
+    let path = Path::new(file_name);
+    let mut name = path.file_stem().unwrap_or(file_name).to_os_string();
+    name.push(format!(" ({})", n));
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    name
+}
+
+/// Collects the names of every file below the subdirectories of `root`, skipping hidden folders.
+pub fn existing_names(root: &Path) -> TakenNames {
+    let mut names = TakenNames::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() && !entry.file_name().to_string_lossy().starts_with('.') {
+                pending.push(entry.path());
+            } else if file_type.is_file() && dir != root {
+                names.entry(entry.file_name()).or_default().insert(entry.path());
+            }
+        }
+    }
+    names
+}
+
+/// Claims `file_name` in `taken` for the file at `owner`, adding a numeric suffix until no other
+/// file holds the name.
+///
+/// A name held only by `owner` itself is free, so a file never collides with its own name.
+pub fn claim_unique(taken: &mut TakenNames, file_name: OsString, owner: &Path) -> OsString {
+    let mut claim = |name: &OsString| {
+        let holders = taken.entry(name.clone()).or_default();
+        let free = holders.iter().all(|holder| holder == owner);
+        if free {
+            holders.insert(owner.to_path_buf());
+        }
+        free
+    };
+    if claim(&file_name) {
+        return file_name;
+    }
+    (1..).map(|n| suffixed(&file_name, n)).find(|candidate| claim(candidate)).unwrap()
+}
@@ -0,0 +1,35 @@
+use std::path::Path;
+
+/// Prints progress messages to stdout at the level chosen with `--quiet` and `--verbose`.
+///
+/// Errors bypass this and always go to stderr.
+#[derive(Clone, Copy)]
+pub struct Output {
+    quiet: bool,
+    verbosity: u8,
+}
+
+impl Output {
+    pub fn new(quiet: bool, verbosity: u8) -> Self {
+        Output { quiet, verbosity }
+    }
+
+    /// Prints a message unless `--quiet` was given.
+    pub fn info(&self, message: impl FnOnce() -> String) {
+        if !self.quiet {
+            println!("{}", message());
+        }
+    }
+
+    /// Prints a message when `--verbose` was given at least `level` times.
+    pub fn verbose(&self, level: u8, message: impl FnOnce() -> String) {
+        if !self.quiet && self.verbosity >= level {
+            println!("{}", message());
+        }
+    }
+}
+
+/// Describes a move as `<verb> <from> -> <to>`, so planned and performed moves read alike.
+pub fn describe_move(verb: &str, from: &Path, to: &Path) -> String {
+    format!("{} {} -> {}", verb, from.display(), to.display())
+}
@@ -0,0 +1,30 @@
+use std::path::Path;
+
+/// Renders planned moves as one `source -> destination` line each, sorted by source.
+///
+/// Sources are relative to `root` and destinations to `destination_root`, with `/` as the
+/// separator on every platform, so the text only changes when placement does.
+pub fn render<'a>(
+    root: &Path,
+    destination_root: &Path,
+    moves: impl IntoIterator<Item = (&'a Path, &'a Path)>,
+) -> String {
+    let mut lines: Vec<(String, String)> = moves
+        .into_iter()
+        .map(|(from, to)| (relative(from, root), relative(to, destination_root)))
+        .collect();
+    lines.sort();
+    lines.iter().map(|(from, to)| format!("{} -> {}\n", from, to)).collect()
+}
+
+/// Writes `path` relative to `root`, or whole if it lies outside it.
+fn relative(path: &Path, root: &Path) -> String {
+    match path.strip_prefix(root) {
+        Ok(relative) => relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        Err(_) => path.to_string_lossy().into_owned(),
+    }
+}
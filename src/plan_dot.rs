@@ -0,0 +1,50 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// Renders the folder tree that `destinations` would create under `root` as a Graphviz graph,
+/// labelling each folder with the number of files planned into it.
+pub fn render<'a>(root: &Path, destinations: impl IntoIterator<Item = &'a Path>) -> String {
+    let mut file_counts: BTreeMap<PathBuf, usize> = BTreeMap::new();
+    let mut edges = BTreeSet::new();
+    for destination in destinations {
+        let folder = destination.parent().unwrap_or(destination);
+        let folder = folder.strip_prefix(root).unwrap_or(folder).to_path_buf();
+        *file_counts.entry(folder.clone()).or_default() += 1;
+
+        let mut child = folder;
+        while let Some(parent) = child.parent() {
+            edges.insert((parent.to_path_buf(), child.clone()));
+            child = parent.to_path_buf();
+        }
+    }
+
+    let mut folders: BTreeSet<&PathBuf> = file_counts.keys().collect();
+    folders.extend(edges.iter().flat_map(|(parent, child)| [parent, child]));
+
+    let mut dot = String::from("digraph plan {\n    rankdir=LR;\n    node [shape=folder];\n");
+    for folder in folders {
+        let name = match folder.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => root.display().to_string(),
+        };
+        let label = match file_counts.get(folder) {
+            Some(1) => format!("{}\n1 file", name),
+            Some(count) => format!("{}\n{} files", name, count),
+            None => name,
+        };
+        dot.push_str(&format!("    {} [label={}];\n", quote(folder), quote_str(&label)));
+    }
+    for (parent, child) in &edges {
+        dot.push_str(&format!("    {} -> {};\n", quote(parent), quote(child)));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn quote(folder: &Path) -> String {
+    quote_str(&format!("/{}", folder.to_string_lossy()))
+}
+
+fn quote_str(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
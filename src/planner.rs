@@ -0,0 +1,168 @@
+//! Works out the date folders a file belongs in.
+
+use crate::week::{iso_week_thursday, start_of_week, week_of_month, week_of_year, WeekStart, WeekSystem};
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime};
+use clap::ValueEnum;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Levels {
+    /// `year/month/week` folders
+    Nested,
+    /// A single `YYYY-MM` folder level
+    #[value(name = "yearmonth")]
+    YearMonth,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Granularity {
+    /// Stop at the `year` folder
+    Year,
+    /// Stop at the `year/month` folder
+    Month,
+    /// `year/month/week` folders
+    Week,
+    /// `year/month/YYYY-MM-DD` folders
+    Day,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum WeekLabel {
+    /// `week of YYYY-MM-DD`, dated by the start of the week
+    Date,
+    /// `week-N`, the ordinal week within the month
+    Ordinal,
+    /// `WNN`, the week number within the year
+    Number,
+}
+
+/// How date folders are laid out and named.
+pub struct DateLayout<'a> {
+    pub levels: Levels,
+    pub granularity: Granularity,
+    pub week_label: WeekLabel,
+    pub week_system: WeekSystem,
+    pub week_start: WeekStart,
+    /// strftime formats for the year, month and week folders, replacing the default names
+    pub year_format: Option<&'a str>,
+    pub month_format: Option<&'a str>,
+    pub week_format: Option<&'a str>,
+    /// Month (1-12) the fiscal year starts in
+    pub fiscal_year_start: Option<u32>,
+}
+
+/// Records the steps of a destination computation for `--explain`.
+pub struct Trace(pub Option<Vec<String>>);
+
+impl Trace {
+    pub fn off() -> Self {
+        Trace(None)
+    }
+
+    pub fn on() -> Self {
+        Trace(Some(Vec::new()))
+    }
+
+    pub fn step(&mut self, step: impl FnOnce() -> String) {
+        if let Some(steps) = &mut self.0 {
+            steps.push(step());
+        }
+    }
+}
+
+/// Builds the `year/month/week of ...` folder for a file dated `datetime`, down to the layout's granularity.
+///
+/// Also returns each folder level with the date it represents.
+pub fn date_folder(
+    datetime: &NaiveDateTime,
+    base_folder: &Path,
+    args: &DateLayout,
+    trace: &mut Trace,
+) -> (PathBuf, Vec<(PathBuf, NaiveDate)>) {
+    // Around New Year an ISO week can belong to the other year, as 2024-12-30 is in W01 of 2025,
+    // so numbered ISO weeks are filed under the year and month of their Thursday
+    let iso_week_number = args.levels != Levels::YearMonth
+        && args.granularity == Granularity::Week
+        && args.week_format.is_none()
+        && args.week_label == WeekLabel::Number
+        && args.week_system == WeekSystem::Iso;
+    let folder_date = if iso_week_number { iso_week_thursday(datetime.date()) } else { datetime.date() };
+    let year = folder_date.year();
+    let month = folder_date.month();
+    let first_of_month = folder_date.with_day(1).unwrap();
+
+    if args.levels == Levels::YearMonth {
+        let folder_name = format!("{:04}-{:02}", year, month);
+        trace.step(|| format!("folders: {}", folder_name));
+        let folder = base_folder.join(folder_name);
+        return (folder.clone(), vec![(folder, first_of_month)]);
+    }
+
+    #[allow(deprecated)]
+    let month_name = match args.month_format {
+        Some(format) => first_of_month.format(format).to_string(),
+        None => chrono::TimeZone::ymd(&Local, year, month, 1).format("%B").to_string(),
+    };
+    // In a fiscal year, months are numbered from its first month so they sort in fiscal order
+    let (year, year_start, month_name) = match args.fiscal_year_start {
+        Some(start_month) => {
+            let fiscal_year = if month >= start_month { year } else { year - 1 };
+            let fiscal_month = (month + 12 - start_month) % 12 + 1;
+            trace.step(|| format!("fiscal year: {}, month {} of it", fiscal_year, fiscal_month));
+            let year_start = NaiveDate::from_ymd_opt(fiscal_year, start_month, 1).unwrap();
+            (fiscal_year, year_start, format!("{:02}-{}", fiscal_month, month_name))
+        }
+        None => (year, first_of_month.with_month(1).unwrap(), month_name),
+    };
+    let year_name = match args.year_format {
+        Some(format) => year_start.format(format).to_string(),
+        None => year.to_string(),
+    };
+    let year_folder = base_folder.join(&year_name);
+    let month_folder = year_folder.join(&month_name);
+    let mut folder_dates = vec![(year_folder.clone(), year_start)];
+
+    match args.granularity {
+        Granularity::Year => {
+            trace.step(|| format!("folders: {}", year_name));
+            return (year_folder, folder_dates);
+        }
+        Granularity::Month => {
+            trace.step(|| format!("folders: {} / {}", year_name, month_name));
+            folder_dates.push((month_folder.clone(), first_of_month));
+            return (month_folder, folder_dates);
+        }
+        Granularity::Day => {
+            let day_name = datetime.format("%Y-%m-%d").to_string();
+            trace.step(|| format!("folders: {} / {} / {}", year_name, month_name, day_name));
+            let day_folder = month_folder.join(day_name);
+            folder_dates.push((month_folder, first_of_month));
+            folder_dates.push((day_folder.clone(), datetime.date()));
+            return (day_folder, folder_dates);
+        }
+        Granularity::Week => {}
+    }
+
+    let week_start = start_of_week(datetime, args.week_start);
+
+    let (week_folder_name, week_date) = match (args.week_format, args.week_label) {
+        (Some(format), _) => (week_start.format(format).to_string(), week_start.date()),
+        (None, WeekLabel::Date) => (format!("week of {}", week_start.format("%Y-%m-%d")), week_start.date()),
+        (None, WeekLabel::Ordinal) => (
+            format!("week-{}", week_of_month(datetime, args.week_system, args.week_start)),
+            week_start.date().max(first_of_month),
+        ),
+        (None, WeekLabel::Number) => (
+            format!("W{:02}", week_of_year(datetime, args.week_system, args.week_start)),
+            week_start.date(),
+        ),
+    };
+
+    trace.step(|| format!("week start: {} ({})", week_start.format("%A"), week_start.format("%Y-%m-%d")));
+    trace.step(|| format!("folders: {} / {} / {}", year_name, month_name, week_folder_name));
+
+    let week_folder = month_folder.join(&week_folder_name);
+    folder_dates.push((month_folder, first_of_month));
+    folder_dates.push((week_folder.clone(), week_date));
+    (week_folder, folder_dates)
+}
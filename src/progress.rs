@@ -0,0 +1,69 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// Live counters for the move phase of a run, reported on `SIGUSR1`.
+pub struct Progress {
+    started: Instant,
+    done: AtomicUsize,
+    in_flight: AtomicUsize,
+    errors: AtomicUsize,
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Progress {
+            started: Instant::now(),
+            done: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            errors: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn start(&self) {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Marks a started move as over, counting it as done if the file was moved.
+    pub fn finish(&self, moved: bool) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        if moved {
+            self.done.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn errors(&self) -> usize {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// Describes the progress so far as a single line.
+    pub fn report(&self) -> String {
+        let done = self.done.load(Ordering::Relaxed);
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 { done as f64 / elapsed } else { 0.0 };
+        format!(
+            "progress: {} done, {} in flight, {} errors, {:.1} files/s",
+            done,
+            self.in_flight.load(Ordering::Relaxed),
+            self.errors.load(Ordering::Relaxed),
+            rate
+        )
+    }
+}
+
+/// A progress bar over `total` files, or a hidden one unless `visible`.
+///
+/// When the number of files is only discovered as the run goes, start from 0 and grow it
+/// with `inc_length`.
+pub fn bar(total: u64, visible: bool) -> ProgressBar {
+    if !visible {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(total);
+    bar.set_style(ProgressStyle::with_template("{bar:40} {pos}/{len} files ({eta})").unwrap());
+    bar
+}
@@ -0,0 +1,88 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{NaiveDate, NaiveDateTime};
+use globset::{Glob, GlobMatcher};
+use std::fmt::Write;
+use std::path::{Component, Path, PathBuf};
+
+const EXPECTED_HEADER: [&str; 2] = ["pattern", "destination_template"];
+
+/// A single `pattern,destination_template` row from a rules CSV.
+pub struct Rule {
+    pattern: GlobMatcher,
+    destination_template: String,
+}
+
+impl Rule {
+    pub fn pattern(&self) -> &str {
+        self.pattern.glob().glob()
+    }
+
+    /// Renders the destination folder for a file dated `datetime`.
+    pub fn destination(&self, datetime: &NaiveDateTime) -> PathBuf {
+        PathBuf::from(datetime.format(&self.destination_template).to_string())
+    }
+}
+
+/// Loads rules from a CSV file, validating the header and every template.
+///
+/// Templates must be relative paths without `..`, so every rule lands inside the target directory.
+pub fn load_csv(path: &Path) -> Result<Vec<Rule>, String> {
+    let mut reader = csv::Reader::from_path(path)
+        .map_err(|err| format!("Failed to read rules CSV {}: {}", path.display(), err))?;
+
+    let header = reader
+        .headers()
+        .map_err(|err| format!("Failed to read rules CSV header: {}", err))?;
+    if header.iter().map(str::trim).ne(EXPECTED_HEADER) {
+        return Err(format!(
+            "Rules CSV header must be `{}`, found `{}`",
+            EXPECTED_HEADER.join(","),
+            header.iter().collect::<Vec<_>>().join(",")
+        ));
+    }
+
+    let mut rules = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        let line = index + 2;
+        let record = record.map_err(|err| format!("Invalid rules CSV row on line {}: {}", line, err))?;
+        let pattern = record[0].trim();
+        let destination_template = record[1].trim();
+
+        let pattern = Glob::new(pattern)
+            .map_err(|err| format!("Invalid pattern on line {}: {}", line, err))?
+            .compile_matcher();
+        if StrftimeItems::new(destination_template).any(|item| item == Item::Error) {
+            return Err(format!("Invalid destination template on line {}: {}", line, destination_template));
+        }
+        // Some fields parse but can't be rendered from a date and time alone, such as the time zone in `%Z`
+        let sample = NaiveDate::from_ymd_opt(2024, 3, 11).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        if write!(String::new(), "{}", sample.format(destination_template)).is_err() {
+            return Err(format!(
+                "Destination template on line {} uses a field that can't be filled from a file's date: {}",
+                line, destination_template
+            ));
+        }
+        // Templates are joined onto the target directory, so they must not be able to leave it
+        let escapes = Path::new(destination_template)
+            .components()
+            .any(|component| matches!(component, Component::RootDir | Component::Prefix(_) | Component::ParentDir));
+        if escapes {
+            return Err(format!(
+                "Destination template on line {} must stay inside the target directory: {}",
+                line, destination_template
+            ));
+        }
+
+        rules.push(Rule {
+            pattern,
+            destination_template: destination_template.to_string(),
+        });
+    }
+
+    Ok(rules)
+}
+
+/// Returns the first rule whose pattern matches the file name.
+pub fn find<'a>(rules: &'a [Rule], file_name: &Path) -> Option<&'a Rule> {
+    rules.iter().find(|rule| rule.pattern.is_match(file_name))
+}
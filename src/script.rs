@@ -0,0 +1,56 @@
+use std::collections::BTreeSet;
+use std::io;
+use std::path::Path;
+use tokio::fs;
+
+/// Quotes a path for POSIX shells by wrapping it in single quotes.
+pub fn shell_quote(path: &Path) -> Vec<u8> {
+    let mut quoted = vec![b'\''];
+    for &byte in path.as_os_str().as_encoded_bytes() {
+        if byte == b'\'' {
+            quoted.extend_from_slice(b"'\\''");
+        } else {
+            quoted.push(byte);
+        }
+    }
+    quoted.push(b'\'');
+    quoted
+}
+
+/// Writes a shell script that performs the given moves with `mkdir -p` and `mv`, or copies them
+/// with `cp -p` when `copy` is set.
+///
+/// Unless `overwrite` is set, files already at a destination are left alone (`-n`), since the
+/// script can't apply the other `--on-conflict` policies itself.
+pub async fn write<'a>(
+    path: &Path,
+    moves: impl Iterator<Item = (&'a Path, &'a Path)> + Clone,
+    copy: bool,
+    overwrite: bool,
+) -> io::Result<()> {
+    let mut script = b"#!/bin/sh\nset -e\n\n".to_vec();
+
+    let folders: BTreeSet<&Path> = moves.clone().filter_map(|(_, to)| to.parent()).collect();
+    for folder in folders {
+        script.extend_from_slice(b"mkdir -p ");
+        script.extend(shell_quote(folder));
+        script.push(b'\n');
+    }
+    script.push(b'\n');
+
+    let command: &[u8] = match (copy, overwrite) {
+        (false, true) => b"mv -- ",
+        (false, false) => b"mv -n -- ",
+        (true, true) => b"cp -p -- ",
+        (true, false) => b"cp -p -n -- ",
+    };
+    for (from, to) in moves {
+        script.extend_from_slice(command);
+        script.extend(shell_quote(from));
+        script.push(b' ');
+        script.extend(shell_quote(to));
+        script.push(b'\n');
+    }
+
+    fs::write(path, script).await
+}
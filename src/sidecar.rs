@@ -0,0 +1,44 @@
+use chrono::{DateTime, NaiveDateTime};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Extension appended to a file's name to find its metadata sidecar, as in `photo.jpg.meta`.
+pub const SIDECAR_EXTENSION: &str = "meta";
+
+/// The part of a sidecar file organize reads.
+#[derive(Deserialize)]
+struct Meta {
+    timestamp: Timestamp,
+}
+
+/// A sidecar timestamp: Unix seconds, RFC 3339, or `YYYY-MM-DD HH:MM:SS` taken as UTC.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Timestamp {
+    Seconds(i64),
+    Text(String),
+}
+
+/// The sidecar that belongs to `path`, whether or not it exists.
+pub fn path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_os_string();
+    sidecar.push(".");
+    sidecar.push(SIDECAR_EXTENSION);
+    PathBuf::from(sidecar)
+}
+
+/// Reads the `timestamp` field of the `.meta` JSON file next to `path`, as a UTC date and time.
+///
+/// Returns `None` when there is no sidecar or its timestamp is missing or invalid.
+pub fn timestamp(path: &Path) -> Option<NaiveDateTime> {
+    let contents = std::fs::read_to_string(self::path(path)).ok()?;
+    let meta: Meta = serde_json::from_str(&contents).ok()?;
+
+    match meta.timestamp {
+        Timestamp::Seconds(seconds) => DateTime::from_timestamp(seconds, 0).map(|datetime| datetime.naive_utc()),
+        Timestamp::Text(text) => DateTime::parse_from_rfc3339(&text)
+            .map(|datetime| datetime.naive_utc())
+            .or_else(|_| NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M:%S"))
+            .ok(),
+    }
+}
@@ -0,0 +1,115 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use tokio::fs;
+
+/// Name of the file in the target directory that keeps the previous run's summary.
+pub const STATS_FILE: &str = ".organize-stats.json";
+
+/// Totals collected over a single run.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Summary {
+    pub files_moved: u64,
+    pub bytes_moved: u64,
+    /// False when the run was interrupted before every move was attempted.
+    #[serde(default)]
+    pub completed: bool,
+    /// Files left in place, by a filter, a conflict or a missing date.
+    #[serde(default)]
+    pub files_skipped: u64,
+    /// Files that could not be moved.
+    #[serde(default)]
+    pub files_failed: u64,
+    /// Files moved into each year, by the date they were organized by.
+    #[serde(default)]
+    pub files_per_year: BTreeMap<i32, u64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SummaryFormat {
+    /// Human-readable text
+    Human,
+    /// A single JSON object
+    Json,
+    /// A CSV header followed by one row
+    Csv,
+    /// Print nothing
+    None,
+}
+
+impl Summary {
+    pub fn record_move(&mut self, bytes: u64, year: Option<i32>) {
+        self.files_moved += 1;
+        self.bytes_moved += bytes;
+        if let Some(year) = year {
+            *self.files_per_year.entry(year).or_default() += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: Summary) {
+        self.files_moved += other.files_moved;
+        self.bytes_moved += other.bytes_moved;
+        self.files_skipped += other.files_skipped;
+        self.files_failed += other.files_failed;
+        for (year, count) in other.files_per_year {
+            *self.files_per_year.entry(year).or_default() += count;
+        }
+    }
+
+    /// Prints the summary in the requested format, comparing against `previous` if known.
+    pub fn print(&self, previous: Option<&Summary>, format: SummaryFormat) {
+        match format {
+            SummaryFormat::Human => {
+                if let Some(previous) = previous {
+                    let files_delta = self.files_moved as i64 - previous.files_moved as i64;
+                    let bytes_delta = self.bytes_moved as i64 - previous.bytes_moved as i64;
+                    println!(
+                        "Compared to the previous run: {:+} files, {:+} bytes moved",
+                        files_delta, bytes_delta
+                    );
+                }
+                println!(
+                    "Moved {} files ({} bytes), skipped {}, failed {}.",
+                    self.files_moved, self.bytes_moved, self.files_skipped, self.files_failed
+                );
+                for (year, count) in &self.files_per_year {
+                    println!("  {}: {} file(s)", year, count);
+                }
+                if self.completed && self.files_failed > 0 {
+                    println!("Operation finished with {} failure(s).", self.files_failed);
+                } else if self.completed {
+                    println!("Operation complete!");
+                } else {
+                    println!("Operation interrupted after moving {} files.", self.files_moved);
+                }
+            }
+            SummaryFormat::Json => {
+                println!("{}", serde_json::to_string(self).expect("Failed to serialize summary"));
+            }
+            SummaryFormat::Csv => {
+                println!("files_moved,bytes_moved,completed,files_skipped,files_failed");
+                println!(
+                    "{},{},{},{},{}",
+                    self.files_moved, self.bytes_moved, self.completed, self.files_skipped, self.files_failed
+                );
+            }
+            SummaryFormat::None => {}
+        }
+    }
+}
+
+/// Reads the summary saved by the previous run in `dir`, if there is one.
+pub async fn load_previous(dir: &Path) -> Option<Summary> {
+    let contents = fs::read_to_string(dir.join(STATS_FILE)).await.ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Saves the summary in `dir` so the next run can compare against it.
+pub async fn save(dir: &Path, summary: &Summary) {
+    let stats_path = dir.join(STATS_FILE);
+    let contents = serde_json::to_string_pretty(summary).expect("Failed to serialize summary");
+    if let Err(err) = fs::write(&stats_path, contents).await {
+        eprintln!("Failed to write {}: {}", stats_path.display(), err);
+    }
+}
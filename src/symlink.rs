@@ -0,0 +1,46 @@
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use tokio::fs;
+
+/// Name of the folder `--symlinks-folder` gathers symlinks into.
+pub const SYMLINKS_DIR: &str = "symlinks";
+
+/// Moves the symlink at `link` to `destination`, rewriting a relative target so the link
+/// still points at the same file from its new folder.
+pub async fn relocate(link: &Path, destination: &Path) -> io::Result<()> {
+    let target = fs::read_link(link).await?;
+    let destination_dir = destination.parent().unwrap();
+    let target = if target.is_absolute() {
+        target
+    } else {
+        relative_path(&link.parent().unwrap().join(target), destination_dir)
+    };
+
+    fs::create_dir_all(destination_dir).await?;
+    create_symlink(&target, destination).await?;
+    fs::remove_file(link).await
+}
+
+/// Lexically expresses `path` relative to the directory `from`.
+fn relative_path(path: &Path, from: &Path) -> PathBuf {
+    let path: Vec<Component> = path.components().collect();
+    let from: Vec<Component> = from.components().collect();
+    let common = path.iter().zip(&from).take_while(|(a, b)| a == b).count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    relative.extend(&path[common..]);
+    relative
+}
+
+#[cfg(unix)]
+async fn create_symlink(target: &Path, link: &Path) -> io::Result<()> {
+    fs::symlink(target, link).await
+}
+
+#[cfg(windows)]
+async fn create_symlink(target: &Path, link: &Path) -> io::Result<()> {
+    fs::symlink_file(target, link).await
+}
@@ -0,0 +1,92 @@
+use filetime::FileTime;
+use std::io;
+use std::path::Path;
+use tokio::fs;
+
+/// Moves a file with a rename, falling back to copying it and removing the original when
+/// `from` and `to` are on different filesystems.
+pub async fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to).await {
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => copy_then_remove(from, to).await,
+        result => result,
+    }
+}
+
+/// Copies `from` to `to` and gives the copy the original's modified time.
+pub async fn copy_preserving_mtime(from: &Path, to: &Path) -> io::Result<u64> {
+    let bytes = fs::copy(from, to).await?;
+    let metadata = fs::metadata(from).await?;
+    filetime::set_file_mtime(to, FileTime::from_last_modification_time(&metadata))?;
+    Ok(bytes)
+}
+
+/// Moves a file across filesystems. The original is only removed once the copy is complete,
+/// and a partial copy is cleaned up if copying fails.
+async fn copy_then_remove(from: &Path, to: &Path) -> io::Result<()> {
+    if let Err(err) = copy_preserving_mtime(from, to).await {
+        let _ = fs::remove_file(to).await;
+        return Err(err);
+    }
+    fs::remove_file(from).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Creates an empty scratch directory for one test.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("organize-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn move_file_renames_within_a_filesystem() {
+        let dir = scratch_dir("move-file-test");
+        let (from, to) = (dir.join("a.txt"), dir.join("b.txt"));
+        std::fs::write(&from, "contents").unwrap();
+
+        move_file(&from, &to).await.unwrap();
+
+        let (from_exists, contents) = (from.exists(), std::fs::read_to_string(&to).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(!from_exists);
+        assert_eq!(contents, "contents");
+    }
+
+    #[tokio::test]
+    async fn cross_device_fallback_keeps_contents_and_modified_time() {
+        let dir = scratch_dir("copy-then-remove-test");
+        let (from, to) = (dir.join("a.txt"), dir.join("b.txt"));
+        std::fs::write(&from, "contents").unwrap();
+        let modified = FileTime::from_unix_time(1_700_000_000, 0);
+        filetime::set_file_mtime(&from, modified).unwrap();
+
+        copy_then_remove(&from, &to).await.unwrap();
+
+        let from_exists = from.exists();
+        let contents = std::fs::read_to_string(&to).unwrap();
+        let copied_modified = FileTime::from_last_modification_time(&std::fs::metadata(&to).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(!from_exists);
+        assert_eq!(contents, "contents");
+        assert_eq!(copied_modified, modified);
+    }
+
+    #[tokio::test]
+    async fn failed_fallback_leaves_the_original() {
+        let dir = scratch_dir("copy-then-remove-failure-test");
+        let from = dir.join("a.txt");
+        std::fs::write(&from, "contents").unwrap();
+
+        let result = copy_then_remove(&from, &dir.join("missing/b.txt")).await;
+
+        let from_exists = from.exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(result.is_err());
+        assert!(from_exists);
+    }
+}
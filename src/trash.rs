@@ -0,0 +1,91 @@
+use chrono::{DateTime, Local, NaiveDateTime};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::fs;
+
+/// Name of the folder in the target directory that holds files set aside by a run.
+pub const TRASH_DIR: &str = ".organize-trash";
+
+/// How each run's folder in the trash is named after the time the run started.
+const RUN_FOLDER_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// Returns the trash folder for a run started at `started`.
+pub fn run_folder(root: &Path, started: DateTime<Local>) -> PathBuf {
+    root.join(TRASH_DIR).join(started.format(RUN_FOLDER_FORMAT).to_string())
+}
+
+/// Moves a file into the trash at `trash_path`, creating its folder as needed, and returns
+/// where it landed.
+///
+/// A file already trashed under that name is kept: the new one gets the first free
+/// `name (n).ext` instead.
+pub async fn move_to_trash(path: &Path, trash_path: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(trash_path.parent().unwrap()).await?;
+    let file_name = trash_path.file_name().unwrap();
+    let mut candidate = trash_path.to_path_buf();
+    let mut n = 0;
+    // Creating the name first reserves it, so two files trashed at once can't pick the same one
+    loop {
+        match fs::OpenOptions::new().write(true).create_new(true).open(&candidate).await {
+            Ok(_) => break,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                n += 1;
+                candidate = trash_path.with_file_name(crate::naming::suffixed(file_name, n));
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    if let Err(err) = crate::transfer::move_file(path, &candidate).await {
+        let _ = fs::remove_file(&candidate).await;
+        return Err(err);
+    }
+    Ok(candidate)
+}
+
+/// Permanently deletes the run folders in the trash that were created before `cutoff`,
+/// returning how many files they held.
+///
+/// Folders whose names aren't run timestamps are left alone. Once `cancelled` is set, no
+/// further folders are deleted.
+pub async fn expire(root: &Path, cutoff: NaiveDateTime, cancelled: &AtomicBool) -> io::Result<usize> {
+    let trash = root.join(TRASH_DIR);
+    let mut entries = match fs::read_dir(&trash).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(err),
+    };
+
+    let mut expired = 0;
+    while let Some(entry) = entries.next_entry().await? {
+        if cancelled.load(Ordering::Relaxed) {
+            break;
+        }
+        let name = entry.file_name();
+        let Ok(trashed) = NaiveDateTime::parse_from_str(&name.to_string_lossy(), RUN_FOLDER_FORMAT) else {
+            continue;
+        };
+        if trashed < cutoff && entry.file_type().await?.is_dir() {
+            expired += count_files(&entry.path()).await?;
+            fs::remove_dir_all(entry.path()).await?;
+        }
+    }
+    Ok(expired)
+}
+
+/// Counts the files anywhere under `dir`.
+async fn count_files(dir: &Path) -> io::Result<usize> {
+    let mut count = 0;
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                pending.push(entry.path());
+            } else {
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
@@ -0,0 +1,68 @@
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime};
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum WeekSystem {
+    /// ISO 8601: weeks start on Monday and week 1 contains the year's first Thursday
+    Iso,
+    /// US: weeks start on the chosen week start, Sunday by default, and week 1 contains January 1st
+    Us,
+}
+
+/// Returns which week of its month a date falls in, counting the week containing the 1st as week 1.
+///
+/// ISO weeks always start on Monday; US weeks start on `week_start`.
+pub fn week_of_month(datetime: &NaiveDateTime, week_system: WeekSystem, week_start: WeekStart) -> u32 {
+    let first_of_month = datetime.date().with_day(1).unwrap();
+    let offset = days_since_start(first_of_month, week_system.week_start(week_start));
+    (datetime.day() - 1 + offset) / 7 + 1
+}
+
+/// Returns the week number of the year under the given numbering system, with US weeks starting on `week_start`.
+pub fn week_of_year(datetime: &NaiveDateTime, week_system: WeekSystem, week_start: WeekStart) -> u32 {
+    match week_system {
+        WeekSystem::Iso => datetime.iso_week().week(),
+        WeekSystem::Us => {
+            let first_of_year = datetime.date().with_ordinal(1).unwrap();
+            let offset = days_since_start(first_of_year, week_start);
+            (datetime.ordinal() - 1 + offset) / 7 + 1
+        }
+    }
+}
+
+impl WeekSystem {
+    /// The day weeks start on, which only the US system leaves to `week_start`.
+    fn week_start(self, week_start: WeekStart) -> WeekStart {
+        match self {
+            WeekSystem::Iso => WeekStart::Monday,
+            WeekSystem::Us => week_start,
+        }
+    }
+}
+
+/// Returns the Thursday of the ISO week containing `date`. The week belongs to the Thursday's
+/// year, so this is the date whose year and month an ISO week number should be filed under.
+pub fn iso_week_thursday(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday().into()) + Duration::days(3)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum WeekStart {
+    /// Weeks run Sunday to Saturday
+    Sunday,
+    /// Weeks run Monday to Sunday
+    Monday,
+}
+
+/// Returns the first day of the week containing `datetime`.
+pub fn start_of_week(datetime: &NaiveDateTime, week_start: WeekStart) -> NaiveDateTime {
+    *datetime - Duration::days(days_since_start(datetime.date(), week_start).into())
+}
+
+/// Returns how many days `date` is past the start of its week.
+fn days_since_start(date: NaiveDate, week_start: WeekStart) -> u32 {
+    match week_start {
+        WeekStart::Sunday => date.weekday().num_days_from_sunday(),
+        WeekStart::Monday => date.weekday().num_days_from_monday(),
+    }
+}